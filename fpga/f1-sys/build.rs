@@ -7,8 +7,13 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let builder = builder
         .file("upstream/fpga_libs/fpga_pci/fpga_pci.c")
         .file("upstream/fpga_libs/fpga_pci/fpga_pci_sysfs.c")
+        .file("upstream/fpga_libs/fpga_dma/fpga_dma_utils.c")
+        .file("upstream/fpga_libs/fpga_mgmt/fpga_mgmt.c")
+        .file("upstream/fpga_libs/fpga_mgmt/fpga_mgmt_cmd.c")
+        .file("upstream/fpga_libs/fpga_mgmt/fpga_hal_mbox.c")
         .file("upstream/utils/io.c")
         .include("upstream/include")
+        .include("upstream/fpga_libs/fpga_mgmt")
         .opt_level(3)
         .flag("-mavx2")
         // can't fix upstream warnings
@@ -18,6 +23,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let bindings = bindgen::Builder::default()
         .header("upstream/include/fpga_pci.h")
+        .header("upstream/include/fpga_mgmt.h")
+        .header("upstream/include/fpga_dma.h")
         .use_core()
         .ctypes_prefix("cty")
         .rustfmt_bindings(true)
@@ -1,4 +1,4 @@
-use crate::{align::HalfAligned, Aligned, Error, Flush, ReadWrite, Result, Write};
+use crate::{align::HalfAligned, Aligned, Dma, Error, Flush, Identify, ReadWrite, Result, Write};
 
 use core::arch::x86_64::{
     // 256-bit SIMD register, requires avx
@@ -11,13 +11,22 @@ use cyclone_f1_sys::{
     c_void, fpga_pci_attach, fpga_pci_detach, fpga_pci_get_address, fpga_pci_peek, fpga_pci_poke,
 };
 
+use std::sync::Arc;
+
+pub mod dma;
+pub mod mgmt;
+
 #[derive(Clone)]
 /// AWS F1 FPGA.
 pub struct F1 {
+    slot: i32,
     ctrl_bar: i32,
     ctrl_offset: u64,
     stream_bar: i32,
     stream_slice: &'static [u256],
+    // host-to-card queue, if the XDMA driver is loaded; users decide whether the
+    // loaded image supports DMA
+    dma: Option<Arc<dma::Queue>>,
 }
 
 impl Drop for F1 {
@@ -29,6 +38,8 @@ impl Drop for F1 {
     }
 }
 
+pub use crate::sysfs::{enumerate, Builder, Offsets, PciAddress, PciIds, Slot};
+
 pub type Packet = Aligned<[u64; 8]>;
 
 pub type Stream<'a, B> = crate::Stream<'a, F1, B>;
@@ -37,15 +48,27 @@ const FPGA_APP_PF: i32 = 0;
 const APP_PF_BAR0: i32 = 0;
 const APP_PF_BAR4: i32 = 4;
 const BURST_CAPABLE: u32 = 1;
+const DMA_CHANNEL: i32 = 0;
 
 #[cfg(feature = "f1")]
 impl F1 {
+    /// Attach to the given slot.
+    ///
+    /// Also opens a DMA queue if the XDMA driver is loaded, and fails if that is not
+    /// possible, e.g. due to permissions or another process using the queue.
     pub fn new(
         slot: i32,
         ctrl_offset: usize,
         stream_offset: usize,
         stream_size: usize,
     ) -> Result<Self> {
+        let dma = match dma::Queue::write_queue(slot, DMA_CHANNEL) {
+            Ok(queue) => Some(Arc::new(queue)),
+            // the XDMA driver is not loaded
+            Err(Error::Dma(err)) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+
         unsafe {
             // fpga_pci_init does not actually do anything
 
@@ -76,15 +99,90 @@ impl F1 {
             let stream_slice = core::slice::from_raw_parts(stream_addr as *const u256, stream_size);
 
             Ok(F1 {
+                slot,
                 ctrl_bar,
                 ctrl_offset: ctrl_offset as u64,
                 stream_bar,
                 stream_slice,
+                dma,
             })
         }
     }
 }
 
+/// List the F1 slots, with the images loaded on them as reported by [`mgmt::describe`].
+///
+/// Whether another process is attached to a slot is not reported.
+pub fn describe_slots() -> Result<Vec<(Slot, mgmt::ImageInfo)>> {
+    enumerate()?
+        .into_iter()
+        .map(|slot| {
+            let info = mgmt::describe(slot.slot)?;
+            Ok((slot, info))
+        })
+        .collect()
+}
+
+#[cfg(feature = "f1")]
+impl Builder {
+    /// Attach to the first matching slot with a loaded image that can be attached to.
+    ///
+    /// Slots that are busy loading or clearing, or have no image loaded, are skipped.
+    /// Attaching is not exclusive: a slot another process is attached to is not
+    /// detected, and is attached to again.
+    pub fn build(self) -> Result<F1> {
+        let Offsets {
+            ctrl_offset,
+            stream_offset,
+            stream_size,
+        } = self.offsets;
+        let mut error = Error::NoSlotAvailable;
+        for slot in self.candidates()? {
+            match mgmt::describe(slot.slot) {
+                Ok(info) if info.status == mgmt::Status::Loaded => {}
+                Ok(_) => continue,
+                Err(err) => {
+                    error = err;
+                    continue;
+                }
+            }
+            match F1::new(slot.slot, ctrl_offset, stream_offset, stream_size) {
+                Ok(f1) => return Ok(f1),
+                Err(err) => error = err,
+            }
+        }
+        Err(error)
+    }
+}
+
+impl Identify for F1 {
+    /// AGFI of the loaded image, via the slot's mailbox PF.
+    ///
+    /// Fails if the slot is busy, or not loaded.
+    fn image_id(&self) -> Result<Option<String>> {
+        let info = mgmt::describe(self.slot)?;
+        match info.status {
+            mgmt::Status::Loaded => info.afi_id.map(Some).ok_or(Error::NoImage(self.slot)),
+            mgmt::Status::Busy => Err(Error::SlotBusy(self.slot)),
+            _ => Err(Error::NoImage(self.slot)),
+        }
+    }
+
+    fn slot(&self) -> Option<i32> {
+        Some(self.slot)
+    }
+}
+
+impl Dma for F1 {
+    fn has_dma(&self) -> bool {
+        self.dma.is_some()
+    }
+
+    fn dma_write(&mut self, address: u64, data: &[u8]) -> Result<()> {
+        self.dma.as_ref().ok_or(Error::NoDma)?.write(address, data)
+    }
+}
+
 impl Flush for F1 {
     fn flush(&mut self) {
         unsafe {
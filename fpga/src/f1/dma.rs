@@ -0,0 +1,83 @@
+//! DMA transfers to the FPGA's DDR, via the XDMA driver and the vendored `fpga_dma` library.
+//!
+//! The queues are the character devices `/dev/xdma<N>_h2c_<channel>` (writes) and
+//! `/dev/xdma<N>_c2h_<channel>` (reads), which only exist if the XDMA driver is loaded.
+
+use std::{fs::File, io, os::unix::io::AsRawFd as _, os::unix::io::FromRawFd as _};
+
+use cyclone_f1_sys::{
+    fpga_dma_burst_read, fpga_dma_burst_write, fpga_dma_driver_FPGA_DMA_XDMA, fpga_dma_open_queue,
+};
+
+use crate::{Error, Result};
+
+/// Number of DMA channels of the XDMA driver.
+pub const CHANNELS: i32 = 4;
+
+/// Convert the `-errno` return code of an `fpga_dma` function.
+fn check(code: i32) -> Result<()> {
+    match code {
+        0 => Ok(()),
+        code => Err(Error::Dma(io::Error::from_raw_os_error(code.abs()))),
+    }
+}
+
+/// DMA queue of an F1 slot, in one direction.
+#[derive(Debug)]
+pub struct Queue {
+    // closed on drop
+    file: File,
+    read: bool,
+}
+
+impl Queue {
+    fn open(slot: i32, channel: i32, read: bool) -> Result<Self> {
+        let fd = unsafe { fpga_dma_open_queue(fpga_dma_driver_FPGA_DMA_XDMA, slot, channel, read) };
+        match fd {
+            // failed `open`, which set errno
+            -1 => return Err(Error::Dma(io::Error::last_os_error())),
+            fd if fd < 0 => check(fd)?,
+            _ => {}
+        }
+        Ok(Queue {
+            file: unsafe { File::from_raw_fd(fd) },
+            read,
+        })
+    }
+
+    /// Open the host-to-card queue of the given channel.
+    pub fn write_queue(slot: i32, channel: i32) -> Result<Self> {
+        Self::open(slot, channel, false)
+    }
+
+    /// Open the card-to-host queue of the given channel.
+    pub fn read_queue(slot: i32, channel: i32) -> Result<Self> {
+        Self::open(slot, channel, true)
+    }
+
+    /// Write `data` to the DDR at byte `address`.
+    pub fn write(&self, address: u64, data: &[u8]) -> Result<()> {
+        assert!(!self.read, "write to card-to-host queue");
+        check(unsafe {
+            fpga_dma_burst_write(
+                self.file.as_raw_fd(),
+                data.as_ptr() as *mut u8,
+                data.len() as _,
+                address as _,
+            )
+        })
+    }
+
+    /// Read `data.len()` bytes from the DDR at byte `address`.
+    pub fn read(&self, address: u64, data: &mut [u8]) -> Result<()> {
+        assert!(self.read, "read from host-to-card queue");
+        check(unsafe {
+            fpga_dma_burst_read(
+                self.file.as_raw_fd(),
+                data.as_mut_ptr(),
+                data.len() as _,
+                address as _,
+            )
+        })
+    }
+}
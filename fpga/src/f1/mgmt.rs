@@ -0,0 +1,221 @@
+//! Management of the AFIs loaded on F1 slots, via the vendored `fpga_mgmt` library.
+//!
+//! Equivalent to `fpga-describe-local-image`, `fpga-load-local-image` and
+//! `fpga-clear-local-image`, and like them requires running as root.
+//!
+//! Loading an AFI may change the application PF, so attach with [`F1::new`][super::F1::new]
+//! only after the load has completed.
+//!
+//! Loading and clearing reset the DDR, so they remove the slot's
+//! [state file][crate::state::state_file].
+
+use core::time::Duration;
+use std::{
+    ffi::{CStr, CString},
+    sync::Once,
+    thread,
+    time::Instant,
+};
+
+use cyclone_f1_sys::{
+    fpga_mgmt_clear_local_image, fpga_mgmt_describe_local_image, fpga_mgmt_image_info,
+    fpga_mgmt_init, fpga_mgmt_load_local_image, fpga_mgmt_strerror, fpga_pci_rescan_slot_app_pfs,
+    FPGA_ERR_AFI_ID_INVALID, FPGA_ERR_FAIL, FPGA_STATUS_BUSY, FPGA_STATUS_CLEARED,
+    FPGA_STATUS_LOADED, FPGA_STATUS_LOAD_FAILED, FPGA_STATUS_NOT_PROGRAMMED,
+};
+
+use crate::{
+    state::remove_state_file,
+    sysfs::{enumerate, PciIds},
+    Error, Result,
+};
+
+/// Polling period while waiting for a load or clear to complete.
+const DELAY: Duration = Duration::from_millis(10);
+
+/// Status of an FPGA slot.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Status {
+    Loaded,
+    Cleared,
+    /// loading or clearing
+    Busy,
+    NotProgrammed,
+    LoadFailed,
+    Unknown(i32),
+}
+
+impl From<i32> for Status {
+    fn from(status: i32) -> Self {
+        match status as u32 {
+            FPGA_STATUS_LOADED => Status::Loaded,
+            FPGA_STATUS_CLEARED => Status::Cleared,
+            FPGA_STATUS_BUSY => Status::Busy,
+            FPGA_STATUS_NOT_PROGRAMMED => Status::NotProgrammed,
+            FPGA_STATUS_LOAD_FAILED => Status::LoadFailed,
+            _ => Status::Unknown(status),
+        }
+    }
+}
+
+/// Description of an FPGA slot, as reported by `fpga-describe-local-image`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImageInfo {
+    pub slot: i32,
+    pub status: Status,
+    /// `FPGA_ERR_*` qualifier of the status, zero if ok
+    pub status_q: i32,
+    /// AGFI of the loaded image, `None` if there is none
+    pub afi_id: Option<String>,
+    /// expected IDs of the application PF
+    pub app_ids: PciIds,
+    pub shell_version: u32,
+}
+
+impl From<&fpga_mgmt_image_info> for ImageInfo {
+    fn from(info: &fpga_mgmt_image_info) -> Self {
+        // null-terminated and zero-padded
+        let afi_id = info.ids.afi_id;
+        let afi_id: Vec<u8> = afi_id
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        let ids = info.ids.afi_device_ids;
+        ImageInfo {
+            slot: info.slot_id,
+            status: info.status.into(),
+            status_q: info.status_q,
+            afi_id: (!afi_id.is_empty()).then(|| String::from_utf8_lossy(&afi_id).into_owned()),
+            app_ids: PciIds {
+                vendor: ids.vendor_id,
+                device: ids.device_id,
+                subsystem_vendor: ids.svid,
+                subsystem_device: ids.ssid,
+            },
+            shell_version: info.sh_version,
+        }
+    }
+}
+
+fn init() {
+    static INIT: Once = Once::new();
+    // only resets the library's mailbox handles, can not fail
+    INIT.call_once(|| unsafe {
+        fpga_mgmt_init();
+    });
+}
+
+/// Convert the return code of an `fpga_mgmt` function.
+fn check(code: i32) -> Result<()> {
+    if code == 0 {
+        return Ok(());
+    }
+    let message = unsafe { CStr::from_ptr(fpga_mgmt_strerror(code)) };
+    Err(Error::Mgmt {
+        code,
+        message: message.to_string_lossy().into_owned(),
+    })
+}
+
+fn afi_id(afi_id: &str) -> Result<CString> {
+    CString::new(afi_id).map_err(|_| check(FPGA_ERR_AFI_ID_INVALID as i32).unwrap_err())
+}
+
+/// Failure of a load or clear whose status is not the expected one.
+fn failed(info: &ImageInfo, reason: String) -> Error {
+    match check(info.status_q) {
+        Err(err) => err,
+        Ok(()) => Error::Mgmt {
+            code: FPGA_ERR_FAIL as i32,
+            message: reason,
+        },
+    }
+}
+
+/// Rescan the application PF, if its PCI IDs or the shell changed, like the synchronous
+/// `fpga_mgmt` functions do.
+fn rescan(info: &ImageInfo, shell_version: u32) -> Result<()> {
+    let app_ids = enumerate()?
+        .into_iter()
+        .find(|slot| slot.slot == info.slot)
+        .and_then(|slot| slot.app_ids);
+    if info.shell_version == shell_version && app_ids == Some(info.app_ids) {
+        return Ok(());
+    }
+    check(unsafe { fpga_pci_rescan_slot_app_pfs(info.slot) })
+}
+
+/// Describe the given slot.
+pub fn describe(slot: i32) -> Result<ImageInfo> {
+    init();
+    unsafe {
+        let mut info: fpga_mgmt_image_info = core::mem::zeroed();
+        check(fpga_mgmt_describe_local_image(slot, &mut info, 0))?;
+        Ok((&info).into())
+    }
+}
+
+/// Start loading the AFI `afi_id`, e.g. `agfi-09bec09a9e2b4d332`, without waiting.
+pub fn load(slot: i32, afi_id: &str) -> Result<()> {
+    init();
+    let afi_id = self::afi_id(afi_id)?;
+    remove_state_file(slot)?;
+    check(unsafe { fpga_mgmt_load_local_image(slot, afi_id.as_ptr() as *mut _) })
+}
+
+/// Load the AFI `afi_id`, and wait until it is loaded and its application PF is rescanned.
+///
+/// Fails with [`Error::SlotBusy`] if the load takes longer than `timeout`.
+pub fn load_sync(slot: i32, afi_id: &str, timeout: Duration) -> Result<ImageInfo> {
+    let shell_version = describe(slot)?.shell_version;
+    load(slot, afi_id)?;
+    let info = wait(slot, timeout)?;
+    if info.status != Status::Loaded {
+        return Err(failed(&info, format!("slot {slot} is {:?}", info.status)));
+    }
+    if info.afi_id.as_deref() != Some(afi_id) {
+        let reason = format!("slot {slot} loaded {:?} instead of {afi_id}", info.afi_id);
+        return Err(failed(&info, reason));
+    }
+    rescan(&info, shell_version)?;
+    Ok(info)
+}
+
+/// Start clearing the slot, without waiting.
+pub fn clear(slot: i32) -> Result<()> {
+    init();
+    remove_state_file(slot)?;
+    check(unsafe { fpga_mgmt_clear_local_image(slot) })
+}
+
+/// Clear the slot, and wait until it is cleared and its application PF is rescanned.
+///
+/// Fails with [`Error::SlotBusy`] if the clear takes longer than `timeout`.
+pub fn clear_sync(slot: i32, timeout: Duration) -> Result<ImageInfo> {
+    let shell_version = describe(slot)?.shell_version;
+    clear(slot)?;
+    let info = wait(slot, timeout)?;
+    if info.status != Status::Cleared {
+        return Err(failed(&info, format!("slot {slot} is {:?}", info.status)));
+    }
+    rescan(&info, shell_version)?;
+    Ok(info)
+}
+
+/// Wait until the slot is no longer busy, e.g. after [`load`] or [`clear`].
+///
+/// Unlike [`load_sync`] and [`clear_sync`], does not rescan the application PF.
+pub fn wait(slot: i32, timeout: Duration) -> Result<ImageInfo> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let info = describe(slot)?;
+        if info.status != Status::Busy {
+            return Ok(info);
+        }
+        if Instant::now() > deadline {
+            return Err(Error::SlotBusy(slot));
+        }
+        thread::sleep(DELAY);
+    }
+}
@@ -18,10 +18,30 @@ pub use f1::F1;
 pub mod null;
 pub use null::Null;
 
+pub mod state;
+
+pub mod sysfs;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("FPGA drivers require running as root.")]
     SudoRequired,
+    #[error("failed to read PCI devices from sysfs")]
+    Sysfs(#[source] std::io::Error),
+    #[error("no FPGA slot matches the selection")]
+    NoSlotAvailable,
+    #[error("FPGA management failed with code {code}: {message}")]
+    Mgmt { code: i32, message: String },
+    #[error("FPGA slot {0} is still busy")]
+    SlotBusy(i32),
+    #[error("FPGA slot {0} has no image loaded")]
+    NoImage(i32),
+    #[error("FPGA has no DMA engine")]
+    NoDma,
+    #[error("DMA transfer failed")]
+    Dma(#[source] std::io::Error),
+    #[error("failed to remove the slot's state file")]
+    StateFile(#[source] std::io::Error),
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -44,9 +64,44 @@ pub trait ReadWrite<V>: Write<V> {
     fn read(&self, index: usize) -> V;
 }
 
+/// Identification of an FPGA and the image loaded on it.
+pub trait Identify {
+    /// ID of the loaded image, e.g. an AGFI on AWS F1, or `None` if the FPGA can not
+    /// tell, e.g. a mock.
+    ///
+    /// Fails if the FPGA can tell, but could not be queried or has no image loaded.
+    fn image_id(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Slot of the FPGA on its host, or `None` if unknown.
+    fn slot(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// Bulk writes to the FPGA's memory, for FPGAs exposing a DMA engine.
+///
+/// The defaults are for FPGAs without DMA.
+pub trait Dma {
+    /// Whether DMA writes are available; whether the loaded image uses the memory
+    /// written to is up to the caller.
+    fn has_dma(&self) -> bool {
+        false
+    }
+
+    /// Write `data` to the FPGA's memory at byte `address`.
+    fn dma_write(&mut self, address: u64, data: &[u8]) -> Result<()> {
+        let _ = (address, data);
+        Err(Error::NoDma)
+    }
+}
+
 /// App-specific backoff mechanism used in streaming.
+///
+/// The backoff is owned by the stream, and may keep state across writes.
 pub trait Backoff<FPGA> {
-    fn backoff(fpga: &mut FPGA, offset: usize);
+    fn backoff(&mut self, fpga: &mut FPGA, offset: usize);
 }
 
 /// Streaming writes to an FPGA.
@@ -55,20 +110,30 @@ pub trait Backoff<FPGA> {
 pub struct Stream<'a, P, FPGA: Write<P>, B = null::Backoff> {
     fpga: &'a mut FPGA,
     offset: usize,
-    __: PhantomData<(B, P)>,
+    backoff: B,
+    __: PhantomData<P>,
 }
 
 /// Marker trait for FPGAs supporting streaming writes.
 pub trait Streamable<'a, P, B: Backoff<Self> = null::Backoff>: Sized + Write<P> {
     /// initialize new stream
-    fn stream(&'a mut self, offset: usize) -> Stream<'a, P, Self, B>;
+    fn stream(&'a mut self, offset: usize) -> Stream<'a, P, Self, B>
+    where
+        B: Default,
+    {
+        self.stream_with(offset, B::default())
+    }
+
+    /// initialize new stream with given backoff
+    fn stream_with(&'a mut self, offset: usize, backoff: B) -> Stream<'a, P, Self, B>;
 }
 
 impl<'a, P, FPGA: Write<P>, B: Backoff<FPGA>> Streamable<'a, P, B> for FPGA {
-    fn stream(&'a mut self, offset: usize) -> Stream<'a, P, FPGA, B> {
+    fn stream_with(&'a mut self, offset: usize, backoff: B) -> Stream<'a, P, FPGA, B> {
         Stream {
             fpga: self,
             offset,
+            backoff,
             __: PhantomData,
         }
     }
@@ -89,6 +154,10 @@ impl<'a, P, FPGA: Write<P>, B> Stream<'a, P, FPGA, B> {
     pub fn offset(&self) -> usize {
         self.offset
     }
+
+    pub fn backoff(&self) -> &B {
+        &self.backoff
+    }
 }
 
 impl<'a, P, FPGA: Write<P>, B: Backoff<FPGA>> Stream<'a, P, FPGA, B> {
@@ -96,6 +165,6 @@ impl<'a, P, FPGA: Write<P>, B: Backoff<FPGA>> Stream<'a, P, FPGA, B> {
     pub fn write(&mut self, packet: &P) {
         self.fpga.write(self.offset, packet);
         self.offset += 1;
-        B::backoff(self.fpga, self.offset);
+        self.backoff.backoff(self.fpga, self.offset);
     }
 }
@@ -1,4 +1,4 @@
-use crate::{Aligned, Flush, ReadWrite, Write};
+use crate::{Aligned, Dma, Flush, Identify, ReadWrite, Write};
 
 #[derive(Copy, Clone)]
 /// Mock FPGA, all writes are suppressed, all reads return default values.
@@ -30,10 +30,15 @@ impl ReadWrite<u32> for Null {
     }
 }
 
+impl Identify for Null {}
+
+impl Dma for Null {}
+
 /// Null backoff
+#[derive(Copy, Clone, Debug, Default)]
 pub struct Backoff;
 impl<F> crate::Backoff<F> for Backoff {
-    fn backoff(_: &mut F, _: usize) {}
+    fn backoff(&mut self, _: &mut F, _: usize) {}
 }
 
 impl<T> Write<Aligned<T>> for Null {
@@ -0,0 +1,27 @@
+//! Host-side state files of FPGA slots, kept by applications such as `cyclone-msm`.
+//!
+//! A slot's state file describes the contents of its DDR, e.g. the loaded points. Loading or
+//! clearing an AFI resets the DDR, so `f1::mgmt` removes the state file; rebooting does too,
+//! and [`STATE_DIR`] is on the `/run` tmpfs, which is emptied on reboot.
+//!
+//! The directory is only accessible to root, which the F1 drivers require anyway.
+
+use std::{io, path::PathBuf};
+
+use crate::{Error, Result};
+
+/// Directory of the state files.
+pub const STATE_DIR: &str = "/run/cyclone-msm";
+
+/// State file of the given slot.
+pub fn state_file(slot: i32) -> PathBuf {
+    PathBuf::from(STATE_DIR).join(format!("slot-{}.state", slot))
+}
+
+/// Remove the state file of the given slot, if there is one.
+pub fn remove_state_file(slot: i32) -> Result<()> {
+    match std::fs::remove_file(state_file(slot)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(Error::StateFile(err)),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,304 @@
+//! Discovery of AWS F1 FPGA slots via sysfs.
+//!
+//! Mirrors `fpga_pci_get_all_slot_specs` of the vendored `fpga_pci_sysfs.c`: each slot
+//! has a mailbox PF with PCI IDs `1d0f:1041`, and its application PF is the PCI device
+//! preceding it. Slots are numbered in order of their mailbox PF's address.
+//!
+//! Unlike the C library, the sysfs root is configurable, and no F1 libraries are needed.
+
+use core::{fmt, str::FromStr};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, Result};
+
+/// Directory of the PCI devices in sysfs.
+pub const SYSFS_PCI_DEVICES: &str = "/sys/bus/pci/devices";
+
+/// Maximum number of slots of an F1 instance (an f1.16xlarge has 8 FPGAs).
+pub const MAX_SLOTS: usize = 8;
+
+/// Vendor and device ID of the mailbox PF.
+const MBOX_IDS: (u16, u16) = (0x1d0f, 0x1041);
+
+/// Address `domain:bus:device.function` of a PCI device.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct PciAddress {
+    pub domain: u16,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{}",
+            self.domain, self.bus, self.device, self.function
+        )
+    }
+}
+
+impl FromStr for PciAddress {
+    type Err = ();
+
+    fn from_str(address: &str) -> core::result::Result<Self, ()> {
+        let (domain, rest) = address.split_once(':').ok_or(())?;
+        let (bus, rest) = rest.split_once(':').ok_or(())?;
+        let (device, function) = rest.split_once('.').ok_or(())?;
+        Ok(PciAddress {
+            domain: u16::from_str_radix(domain, 16).map_err(drop)?,
+            bus: u8::from_str_radix(bus, 16).map_err(drop)?,
+            device: u8::from_str_radix(device, 16).map_err(drop)?,
+            function: function.parse().map_err(drop)?,
+        })
+    }
+}
+
+/// PCI IDs of a physical function.
+///
+/// The IDs of the application PF are set by the loaded AFI.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PciIds {
+    pub vendor: u16,
+    pub device: u16,
+    pub subsystem_vendor: u16,
+    pub subsystem_device: u16,
+}
+
+/// FPGA slot of an F1 instance.
+///
+/// Only what sysfs tells; the loaded AFI and whether the slot is busy are reported by
+/// `f1::describe_slots`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Slot {
+    /// slot number, as used by `fpga_pci_attach`
+    pub slot: i32,
+    pub mbox: PciAddress,
+    pub app: PciAddress,
+    /// IDs of the application PF, `None` if it is missing, e.g. during an AFI load
+    pub app_ids: Option<PciIds>,
+}
+
+/// Read a hexadecimal ID such as `0x1d0f` from a sysfs file.
+fn read_id(path: &Path) -> io::Result<u16> {
+    let id = fs::read_to_string(path)?;
+    let id = id.trim();
+    u16::from_str_radix(id.trim_start_matches("0x"), 16)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("invalid PCI ID {id}")))
+}
+
+fn read_ids(device: &Path) -> io::Result<PciIds> {
+    Ok(PciIds {
+        vendor: read_id(&device.join("vendor"))?,
+        device: read_id(&device.join("device"))?,
+        subsystem_vendor: read_id(&device.join("subsystem_vendor"))?,
+        subsystem_device: read_id(&device.join("subsystem_device"))?,
+    })
+}
+
+/// List the F1 slots of the PCI devices in sysfs.
+pub fn enumerate() -> Result<Vec<Slot>> {
+    enumerate_in(SYSFS_PCI_DEVICES)
+}
+
+/// List the F1 slots of the PCI devices in the given directory.
+pub fn enumerate_in(root: impl AsRef<Path>) -> Result<Vec<Slot>> {
+    let root = root.as_ref();
+    let mut mboxes = Vec::new();
+    for entry in fs::read_dir(root).map_err(Error::Sysfs)? {
+        let name = entry.map_err(Error::Sysfs)?.file_name();
+        // skip anything that is not a PCI device
+        let address = match name.to_str().map(str::parse::<PciAddress>) {
+            Some(Ok(address)) => address,
+            _ => continue,
+        };
+        let ids = match read_ids(&root.join(name)) {
+            Ok(ids) => ids,
+            Err(_) => continue,
+        };
+        if (ids.vendor, ids.device) == MBOX_IDS {
+            mboxes.push(address);
+        }
+    }
+    mboxes.sort();
+    mboxes.truncate(MAX_SLOTS);
+
+    Ok(mboxes
+        .into_iter()
+        .zip(0..)
+        .map(|(mbox, slot)| {
+            let app = PciAddress {
+                device: mbox.device.wrapping_sub(1),
+                ..mbox
+            };
+            let app_ids = read_ids(&root.join(app.to_string())).ok();
+            Slot {
+                slot,
+                mbox,
+                app,
+                app_ids,
+            }
+        })
+        .collect())
+}
+
+/// Offsets of the FPGA app's control registers and stream memory, in BAR0 resp. BAR4.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Offsets {
+    pub ctrl_offset: usize,
+    pub stream_offset: usize,
+    pub stream_size: usize,
+}
+
+impl Default for Offsets {
+    /// Offsets of the Cyclone MSM FPGA app.
+    fn default() -> Self {
+        Self {
+            ctrl_offset: 0x500,
+            stream_offset: 0,
+            stream_size: 0x1_0000_0000,
+        }
+    }
+}
+
+/// Selects an F1 slot to attach to.
+///
+/// By default, any slot with an application PF qualifies. `build` also skips slots
+/// without a loaded image, but can not tell whether another process uses a slot.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    root: PathBuf,
+    slot: Option<i32>,
+    app_ids: Option<PciIds>,
+    pub(crate) offsets: Offsets,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from(SYSFS_PCI_DEVICES),
+            slot: None,
+            app_ids: None,
+            offsets: Offsets::default(),
+        }
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Directory of the PCI devices, defaults to [`SYSFS_PCI_DEVICES`].
+    pub fn sysfs(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Only use the given slot.
+    pub fn slot(mut self, slot: i32) -> Self {
+        self.slot = Some(slot);
+        self
+    }
+
+    /// Only use slots whose loaded AFI has the given application PF IDs.
+    pub fn app_ids(mut self, app_ids: PciIds) -> Self {
+        self.app_ids = Some(app_ids);
+        self
+    }
+
+    pub fn offsets(mut self, offsets: Offsets) -> Self {
+        self.offsets = offsets;
+        self
+    }
+
+    /// Slots matching the selection, in order of preference.
+    pub fn candidates(&self) -> Result<Vec<Slot>> {
+        Ok(enumerate_in(&self.root)?
+            .into_iter()
+            .filter(|slot| self.slot.map_or(true, |number| number == slot.slot))
+            .filter(|slot| match (self.app_ids, slot.app_ids) {
+                (_, None) => false,
+                (None, Some(_)) => true,
+                (Some(wanted), Some(ids)) => wanted == ids,
+            })
+            .collect())
+    }
+
+    /// First slot matching the selection.
+    pub fn select(&self) -> Result<Slot> {
+        self.candidates()?
+            .into_iter()
+            .next()
+            .ok_or(Error::NoSlotAvailable)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn device(root: &Path, address: &str, ids: [u16; 4]) {
+        let device = root.join(address);
+        fs::create_dir_all(&device).unwrap();
+        let names = ["vendor", "device", "subsystem_vendor", "subsystem_device"];
+        for (name, id) in names.iter().zip(ids) {
+            fs::write(device.join(name), format!("0x{id:04x}\n")).unwrap();
+        }
+    }
+
+    #[test]
+    fn enumerate_fake_sysfs() {
+        let root = std::env::temp_dir().join(format!("cyclone-sysfs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let afi = [0x1d0f, 0xf000, 0x1d0f, 0x1d51];
+        let other_afi = [0x1d0f, 0xf001, 0x1d0f, 0x1d51];
+        let mbox = [0x1d0f, 0x1041, 0x1d0f, 0x1d51];
+        // slots in reverse order of directory creation, slot 1 without app PF
+        device(&root, "0000:00:1f.0", mbox);
+        device(&root, "0000:00:1e.0", other_afi);
+        device(&root, "0000:00:1b.0", mbox);
+        device(&root, "0000:00:1d.0", mbox);
+        device(&root, "0000:00:1c.0", afi);
+        // unrelated devices, and garbage
+        device(&root, "0000:00:03.0", [0x8086, 0x1237, 0x1af4, 0x1100]);
+        fs::create_dir_all(root.join("not-a-device")).unwrap();
+
+        let slots = enumerate_in(&root).unwrap();
+        let mboxes: Vec<_> = slots.iter().map(|slot| slot.mbox.to_string()).collect();
+        assert_eq!(mboxes, ["0000:00:1b.0", "0000:00:1d.0", "0000:00:1f.0"]);
+        assert_eq!(slots[0].app.to_string(), "0000:00:1a.0");
+        assert_eq!(slots[0].app_ids, None);
+        assert_eq!(slots[1].app_ids.map(|ids| ids.device), Some(0xf000));
+        assert_eq!(slots[2].slot, 2);
+
+        let builder = Builder::new().sysfs(&root);
+        assert_eq!(builder.select().unwrap().slot, 1);
+        let candidates = builder.candidates().unwrap();
+        assert_eq!(candidates.len(), 2);
+
+        let ids = |ids: [u16; 4]| PciIds {
+            vendor: ids[0],
+            device: ids[1],
+            subsystem_vendor: ids[2],
+            subsystem_device: ids[3],
+        };
+        let builder = builder.app_ids(ids(other_afi));
+        assert_eq!(builder.select().unwrap().slot, 2);
+        assert!(matches!(
+            builder.slot(1).select(),
+            Err(Error::NoSlotAvailable)
+        ));
+
+        assert!(matches!(
+            enumerate_in(root.join("missing")),
+            Err(Error::Sysfs(_))
+        ));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
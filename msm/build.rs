@@ -0,0 +1,31 @@
+//! Enables the AVX-512 digit kernel if the "avx512" feature is selected and the
+//! toolchain has stable AVX-512 intrinsics, i.e. Rust 1.89 or later.
+
+use std::{env, process::Command};
+
+/// First Rust version with stable AVX-512 intrinsics and `target_feature(enable = "avx512f")`.
+const AVX512_MINOR: u32 = 89;
+
+fn rustc_minor() -> Option<u32> {
+    let rustc = env::var_os("RUSTC")?;
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    // "rustc 1.89.0 (...)"
+    let version = String::from_utf8(output.stdout).ok()?;
+    let version = version.split_whitespace().nth(1)?;
+    version.split('.').nth(1)?.parse().ok()
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(cyclone_avx512)");
+    if env::var_os("CARGO_FEATURE_AVX512").is_none() {
+        return;
+    }
+    match rustc_minor() {
+        Some(minor) if minor >= AVX512_MINOR => println!("cargo:rustc-cfg=cyclone_avx512"),
+        _ => println!(
+            "cargo:warning=the \"avx512\" feature needs Rust 1.{}, building without the AVX-512 kernel",
+            AVX512_MINOR
+        ),
+    }
+}
@@ -7,11 +7,11 @@ fn main() {
     const SIZE: u8 = 1;
 
     let f1 = fpga().unwrap();
-    let mut app = App::new(f1, SIZE);
+    let mut app = App::new(f1, SIZE).unwrap();
     let points = timed("generating random points", || random_points(SIZE));
     let expected = points[0] + points[1];
 
-    app.set_points(&points);
+    app.set_points(&points).unwrap();
 
     let mut stream = app.start_column();
     let mut packet = Packet::default();
@@ -19,6 +19,6 @@ fn main() {
     packet[1] = Command::set_digit(1);
     stream.write(&packet);
 
-    let point = into_weierstrass(&app.get_point());
+    let point = into_weierstrass(&app.get_point().unwrap());
     assert_eq!(expected, point);
 }
@@ -7,17 +7,17 @@ fn main() {
     const SIZE: u8 = 0;
 
     let f1 = fpga().unwrap();
-    let mut app = App::new(f1, SIZE);
+    let mut app = App::new(f1, SIZE).unwrap();
     let points = timed("generating random points", || random_points(SIZE));
     let expected = -points[0];
 
-    app.set_points(&points);
+    app.set_points(&points).unwrap();
 
     let mut stream = app.start_column();
     let mut packet = Packet::default();
     packet[0] = Command::set_digit(-1);
     stream.write(&packet);
 
-    let point = into_weierstrass(&app.get_point());
+    let point = into_weierstrass(&app.get_point().unwrap());
     assert_eq!(expected, point);
 }
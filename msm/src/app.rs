@@ -1,34 +1,55 @@
 //! Host-side app to interact with FPGA app.
-use core::iter;
+use core::{iter, ops::Range};
+use std::{
+    path::PathBuf,
+    sync::mpsc,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
 
-use ark_bls12_377::{Fq, Fr, G1Affine, G1TEProjective};
+use ark_bls12_377::{Fq, Fr, G1Affine, G1TEAffine, G1TEProjective};
 use ark_std::Zero;
 
-use fpga::{null::Backoff as NullBackoff, Flush as _, ReadWrite as _, Streamable as _, Write as _};
+use fpga::{null::Backoff as NullBackoff, Dma, Flush, Identify, ReadWrite, Streamable as _, Write};
 
+/// Default FPGA backend, selected by the "hw" feature.
 #[cfg(not(feature = "hw"))]
-pub use fpga::Null as Fpga;
+pub use crate::sim::Sim as Fpga;
+/// Default FPGA backend, selected by the "hw" feature.
 #[cfg(feature = "hw")]
 pub use fpga::F1 as Fpga;
 
 use crate::{
     bls12_377::{into_weierstrass, G1PTEAffine},
-    precompute::{limb_carries, single_digit_carry},
-    timing::timed,
-    App, Command, G1Projective, Packet, Scalar,
+    io::MappedPoints,
+    precompute::{DigitWidth, Digits, DIGITS_PER_PACKET},
+    state::{state_file, Fingerprint, FingerprintHasher, State},
+    timing::{timed, Span},
+    App, Command, Error, G1Projective, Packet, Result, Scalar,
 };
 
-const DDR_READ_LEN: u32 = 64;
+/// Largest supported MSM size, as power of two.
+pub const MAX_SIZE: u8 = 27;
+/// Largest supported MSM length.
+pub const MAX_LEN: usize = 1 << MAX_SIZE;
+
+/// AGFI of the FPGA image this app is written for.
+pub const IMAGE: &str = "agfi-09bec09a9e2b4d332";
+/// AGFI of the ZPrize submission, which can not read out column sums without DRAM.
+pub const ZPRIZE_IMAGE: &str = "agfi-0d25a1d127f1b497f";
 
-const NUM_BUCKETS: u32 = 1 << 15;
-const FIRST_BUCKET: u32 = 0;
-const LAST_BUCKET: u32 = NUM_BUCKETS - 1;
+const DDR_READ_LEN: u32 = 64;
+/// Points per DMA transfer when loading points.
+const DMA_CHUNK_POINTS: usize = 1 << 14;
+/// Packed points fill whole packets in groups of 4 points per 9 packets.
+const PACKED_GROUP_POINTS: usize = 4;
+const PACKED_GROUP_PACKETS: usize = 9;
 
 const BACKOFF_THRESHOLD: u32 = 64;
 const SET_POINTS_FLUSH_EVERY: usize = 1024;
 const SET_DIGITS_FLUSH_BACKOFF_EVERY: usize = 512;
 
-type FpgaStream<'a, B> = fpga::Stream<'a, Packet, Fpga, B>;
+type FpgaStream<'a, F, B> = fpga::Stream<'a, Packet, F, B>;
 
 fn shl_assign(point: &mut G1TEProjective, c: usize) {
     use ark_ec::Group as _;
@@ -48,8 +69,74 @@ pub enum Stream {
     // must start with Command::Start, then packets of Command::SetDigit
     Msm = 4 << 26,
     SetZero = 5 << 26,
+    // coordinates of consecutive points, cf. PointFormat::Packed
+    SetPoints = 6 << 26,
+}
+
+/// Format of points streamed to the FPGA via MMIO.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PointFormat {
+    /// X, Y and kT in three passes over the points, one coordinate per packet,
+    /// its last two limbs being padding.
+    #[default]
+    Padded,
+    /// X, Y and kT of consecutive points as one stream of limbs, without padding,
+    /// i.e. 4 points per 9 packets. Needs an image decoding [`Stream::SetPoints`],
+    /// which so far only the simulated [`SIM_IMAGE`][crate::sim::SIM_IMAGE] does.
+    Packed,
+}
+
+/// Layout of points in the FPGA's DDR, for images that load points via DMA.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DdrLayout {
+    /// byte address of the first point
+    pub base: u64,
+    /// X, Y and kT are in Montgomery form, each zero-padded to this many bytes
+    pub coordinate_bytes: usize,
+}
+
+impl DdrLayout {
+    pub const fn point_bytes(&self) -> usize {
+        3 * self.coordinate_bytes
+    }
+
+    /// Byte address of the point at `index`.
+    pub const fn address(&self, index: usize) -> u64 {
+        self.base + (index * self.point_bytes()) as u64
+    }
+}
+
+/// FPGA image supported by this app.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+    /// AGFI
+    pub id: &'static str,
+    /// supported point formats, the first being the default
+    pub point_formats: &'static [PointFormat],
+    /// DDR layout, if points can be loaded via DMA; otherwise they are streamed via MMIO
+    pub dma: Option<DdrLayout>,
+}
+
+/// FPGA images supported by this app.
+pub const IMAGES: &[Image] = &[Image {
+    id: IMAGE,
+    point_formats: &[PointFormat::Padded],
+    dma: None,
+}];
+
+/// FPGA backend, and the images this app supports on it.
+pub trait Images: Identify {
+    /// By default the [`IMAGES`]; simulators may add images under development.
+    fn images(&self) -> &'static [Image] {
+        IMAGES
+    }
 }
 
+#[cfg(feature = "hw")]
+impl Images for fpga::F1 {}
+
+impl Images for fpga::Null {}
+
 impl Command {
     #[inline(always)]
     pub fn set_digit(digit: i16) -> u64 {
@@ -104,88 +191,764 @@ pub struct Statistics {
     pub ddr_read_count_channel_3: u32,
 }
 
-impl App {
-    pub fn new(fpga: Fpga, size: u8) -> Self {
-        assert!(size <= 27);
+/// Condition the host polls the FPGA for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Wait {
+    /// digits queue draining below the backoff threshold during a column
+    DigitsQueue,
+    /// column sum being aggregated at the end of a column
+    Aggregation,
+}
+
+/// Deadlines when polling the FPGA, `None` waits indefinitely.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Timeouts {
+    /// Maximum wait for the digits queue to drain, per backoff.
+    pub digits_queue: Option<Duration>,
+    /// Maximum wait for a column sum to be aggregated.
+    pub aggregation: Option<Duration>,
+    /// Re-initialize the FPGA app via [`App::initialize`] after a timeout.
+    pub recover: bool,
+}
+
+/// Column of an MSM, within a batch of MSMs.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ColumnId {
+    pub instance: usize,
+    /// index of the digit, most significant columns are streamed first
+    pub column: usize,
+    /// pass over a part of the bucket range, see [`App::set_bucket_passes`]
+    pub pass: usize,
+}
+
+/// Host-side activities of an MSM, see [`App::timeline`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Activity {
+    /// streaming the digits of a column
+    Stream(ColumnId),
+    /// reading back the sum of a column
+    Readback(ColumnId),
+    /// folding the sum of a column into the instance's total
+    Fold(ColumnId),
+}
+
+/// Incremental read-back of an aggregated column sum.
+///
+/// Assumes the FPGA app keeps the sum in the coordinate registers until the next
+/// aggregation, so it can be read back while the following column is streamed,
+/// as long as it is complete before the last packet of that column.
+struct Readback {
+    column: ColumnId,
+    start: Instant,
+    end: Instant,
+    words: [[u32; 12]; 4],
+    read: usize,
+}
+
+impl Readback {
+    const COORDINATES: [ReadRegister; 4] = [
+        ReadRegister::X,
+        ReadRegister::Y,
+        ReadRegister::Z,
+        ReadRegister::T,
+    ];
+    const WORDS: usize = 4 * 12;
+
+    fn new(column: ColumnId) -> Self {
+        let start = Instant::now();
+        Self {
+            column,
+            start,
+            end: start,
+            words: Default::default(),
+            read: 0,
+        }
+    }
+
+    /// Read the next word of the column sum, if any are left.
+    #[inline]
+    fn step(&mut self, fpga: &mut impl ReadWrite<u32>) {
+        if self.read < Self::WORDS {
+            let (coordinate, word) = (self.read / 12, self.read % 12);
+            fpga.write(WriteRegister::Query as _, &(word as u32));
+            self.words[coordinate][word] = fpga.read(Self::COORDINATES[coordinate] as _);
+            self.read += 1;
+            if self.read == Self::WORDS {
+                self.end = Instant::now();
+            }
+        }
+    }
+
+    /// Read the remaining words of the column sum.
+    fn complete(&mut self, fpga: &mut impl ReadWrite<u32>) {
+        while self.read < Self::WORDS {
+            self.step(fpga);
+        }
+    }
+
+    /// Column sum of a completed read-back, and the time it took.
+    fn finish(self) -> (G1TEProjective, Span<Activity>) {
+        debug_assert_eq!(self.read, Self::WORDS);
+        let [x, y, z, t] = self.words.map(|words| {
+            let mut buffer = [0u64; 6];
+            for (limb, words) in buffer.iter_mut().zip(words.chunks(2)) {
+                // | has lower precedence than <<, whereas + has higher
+                // and would need parentheses
+                *limb = (words[1] as u64) << 32 | words[0] as u64;
+            }
+            Fq::from(ark_ff::BigInt(buffer))
+        });
+
+        let mut point = G1TEProjective::zero();
+        point.x = x;
+        point.y = y;
+        point.z = z;
+        point.t = t;
+
+        let span = Span {
+            activity: Activity::Readback(self.column),
+            start: self.start,
+            end: self.end,
+        };
+        (point, span)
+    }
+}
+
+/// Column sums in flight: the last aggregated column, and the thread folding read back sums.
+///
+/// Folding is Horner's rule per instance, in steps of the digit width, after adding up
+/// the partial sums of all passes of a column. Totals are finally shifted to the lowest
+/// streamed column.
+struct Pipeline {
+    pending: Option<ColumnId>,
+    points: mpsc::Sender<(ColumnId, G1TEProjective)>,
+    folder: JoinHandle<(Vec<G1TEProjective>, Vec<Span<Activity>>)>,
+}
+
+impl Pipeline {
+    fn new(width: DigitWidth, passes: usize, lowest: usize) -> Self {
+        let (points, columns) = mpsc::channel::<(ColumnId, G1TEProjective)>();
+        let folder = std::thread::spawn(move || {
+            let mut totals = Vec::new();
+            let mut spans = Vec::new();
+            for (column, point) in columns {
+                let start = Instant::now();
+                if totals.len() <= column.instance {
+                    totals.resize(column.instance + 1, G1TEProjective::zero());
+                }
+                let total = &mut totals[column.instance];
+                *total += point;
+                if column.column != lowest && column.pass + 1 == passes {
+                    shl_assign(total, width.bits() as usize);
+                }
+                spans.push(Span::until_now(Activity::Fold(column), start));
+            }
+            for total in totals.iter_mut() {
+                shl_assign(total, width.bits() as usize * lowest);
+            }
+            (totals, spans)
+        });
+
+        Self {
+            pending: None,
+            points,
+            folder,
+        }
+    }
+
+    /// Totals per instance, and the folding activities.
+    fn finish(self) -> (Vec<G1TEProjective>, Vec<Span<Activity>>) {
+        drop(self.points);
+        self.folder
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+impl<F: Dma + ReadWrite<u32> + Write<Packet>> App<F> {
+    /// Load the points into the FPGA's DDR.
+    ///
+    /// Uses DMA if both the image and the FPGA support it, otherwise streams the
+    /// coordinates via MMIO. Records the points' fingerprint in the state file,
+    /// cf. [`App::attach_preloaded`].
+    pub fn set_preprocessed_points(&mut self, points: &[G1PTEAffine]) -> Result<()> {
+        self.upload_points(points)?;
+        self.remember_points(Fingerprint::of(points))
+    }
+
+    /// Load the points of a mapped point file into the FPGA's DDR.
+    ///
+    /// The upload starts while the file is paging in, and the points are verified
+    /// against the file's checksum once they are all in memory.
+    pub fn set_mapped_points(&mut self, points: &MappedPoints) -> Result<()> {
+        self.upload_points(points.points())?;
+        let fingerprint = points.verify()?;
+        self.remember_points(fingerprint)
+    }
+
+    /// Load the points into the FPGA's DDR piecewise, from chunks of consecutive points,
+    /// e.g. of [`preprocess_chunks`][crate::preprocess::preprocess_chunks].
+    ///
+    /// Holds at most one chunk in memory. The chunks must fill the MSM length.
+    pub fn set_preprocessed_chunks(
+        &mut self,
+        chunks: impl IntoIterator<Item = Result<Vec<G1PTEAffine>>>,
+    ) -> Result<()> {
+        self.forget_points()?;
+        let mut hasher = FingerprintHasher::new();
+        let mut first = 0;
+        // packed points are streamed in whole groups, so a partial group waits for the next chunk
+        let mut pending = Vec::new();
+        for chunk in chunks {
+            let mut chunk = chunk?;
+            hasher.update(&chunk);
+            if !pending.is_empty() {
+                pending.extend_from_slice(&chunk);
+                chunk = core::mem::take(&mut pending);
+            }
+            pending = chunk.split_off(chunk.len() / PACKED_GROUP_POINTS * PACKED_GROUP_POINTS);
+            if first + chunk.len() + pending.len() > self.len {
+                return Err(Error::LengthMismatch {
+                    expected: self.len,
+                    actual: first + chunk.len() + pending.len(),
+                });
+            }
+            self.upload_points_at(first, &chunk)?;
+            first += chunk.len();
+        }
+        self.upload_points_at(first, &pending)?;
+        self.check_len(first + pending.len())?;
+        self.remember_points(hasher.finish())
+    }
+
+    fn upload_points(&mut self, points: &[G1PTEAffine]) -> Result<()> {
+        self.check_len(points.len())?;
+
+        self.forget_points()?;
+        self.upload_points_at(0, points)
+    }
+
+    /// Upload points to the DDR, starting at index `first`.
+    ///
+    /// Uses DMA if both the image and the FPGA support it, otherwise streams via MMIO.
+    fn upload_points_at(&mut self, first: usize, points: &[G1PTEAffine]) -> Result<()> {
+        match self.image.and_then(|image| image.dma) {
+            Some(layout) if self.fpga.has_dma() => self.dma_points(layout, first, points),
+            _ => {
+                self.stream_points(first, points.iter().copied());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set_points(&mut self, points: &[G1Affine]) -> Result<()> {
+        self.check_len(points.len())?;
+        let preprocessed_points: Vec<_> = points.iter().map(|point| point.into()).collect();
+        self.set_preprocessed_points(&preprocessed_points)
+    }
+
+    fn dma_points(
+        &mut self,
+        layout: DdrLayout,
+        first: usize,
+        points: &[G1PTEAffine],
+    ) -> Result<()> {
+        let point_bytes = layout.point_bytes();
+        let mut buffer = vec![0u8; DMA_CHUNK_POINTS.min(points.len()) * point_bytes];
+        for (i, chunk) in points.chunks(DMA_CHUNK_POINTS).enumerate() {
+            let bytes = &mut buffer[..chunk.len() * point_bytes];
+            for (point, bytes) in chunk.iter().zip(bytes.chunks_exact_mut(point_bytes)) {
+                let coordinates = [point.x, point.y, point.kt];
+                let coordinate_bytes = bytes.chunks_exact_mut(layout.coordinate_bytes);
+                for (coordinate, bytes) in coordinates.iter().zip(coordinate_bytes) {
+                    for (limb, bytes) in coordinate.0.as_ref().iter().zip(bytes.chunks_exact_mut(8))
+                    {
+                        bytes.copy_from_slice(&limb.to_le_bytes());
+                    }
+                }
+            }
+            let address = layout.address(first + i * DMA_CHUNK_POINTS);
+            self.fpga.dma_write(address, bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that the image loaded on the FPGA is one of its [`Images`].
+///
+/// Images of FPGAs that can not tell, e.g. mocks, are accepted, returning `None`.
+/// Fails if the FPGA can tell, but has no image loaded.
+pub fn check_image(fpga: &impl Images) -> Result<Option<&'static Image>> {
+    let id = match fpga.image_id()? {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    match fpga.images().iter().find(|image| image.id == id) {
+        Some(image) => Ok(Some(image)),
+        None => Err(Error::IncompatibleImage {
+            image: id,
+            expected: IMAGE,
+        }),
+    }
+}
+
+impl<F: Images + ReadWrite<u32> + Write<Packet>> App<F> {
+    /// App for MSMs of length `2^size`.
+    ///
+    /// Fails if the FPGA has an incompatible image loaded, cf. [`check_image`];
+    /// otherwise, the point format is the image's default.
+    pub fn new(fpga: F, size: u8) -> Result<Self> {
+        if size > MAX_SIZE {
+            return Err(Error::SizeOutOfRange(size));
+        }
+        Self::with_len(fpga, 1 << size)
+    }
+
+    /// App for MSMs of arbitrary length, up to [`MAX_LEN`].
+    ///
+    /// Only `len` points are stored on the FPGA, and columns are programmed to end after
+    /// `len` digits, so there is no need to pad points or scalars to a power of two.
+    pub fn with_len(fpga: F, len: usize) -> Result<Self> {
+        let image = check_image(&fpga)?;
+        let mut app = Self::with_len_unchecked(fpga, len)?;
+        if let Some(image) = image {
+            app.point_format = image.point_formats[0];
+        }
+        app.image = image;
+        Ok(app)
+    }
+
+    /// Like [`App::with_len`], without checking the loaded image.
+    ///
+    /// For backends that can not be identified, or images under development.
+    /// The point format is [`PointFormat::Padded`]; the state file is the slot's, if any.
+    pub fn with_len_unchecked(fpga: F, len: usize) -> Result<Self> {
+        if len > MAX_LEN {
+            return Err(Error::LengthOutOfRange(len));
+        }
+        let slot = fpga.slot();
         let mut app = App {
             fpga,
-            len: 1 << size,
-            carried: Some(vec![Scalar::default(); 1 << size]),
+            len,
+            digits: Some(Digits::new()),
+            next_digits: Digits::new(),
+            width: DigitWidth::default(),
+            columns: 0..DigitWidth::default().columns(),
+            buckets: 0..DigitWidth::default().buckets(),
+            passes: 1,
+            point_format: PointFormat::Padded,
+            image: None,
+            slot,
+            state_file: slot.map(state_file),
+            fingerprint: None,
+            timeouts: Timeouts::default(),
+            overlapped_readback: false,
+            offset: 0,
+            timeline: Vec::new(),
+        };
+        app.initialize();
+
+        Ok(app)
+    }
+}
+
+impl<F: ReadWrite<u32> + Write<Packet>> App<F> {
+    /// Re-run the register initialization of [`App::new`], e.g. to recover from a timeout.
+    pub fn initialize(&mut self) {
+        self.set_size();
+        self.set_bucket_registers(self.buckets.clone());
+        self.set_ddr_read_len();
+        self.set_zero();
+    }
+
+    pub fn point_format(&self) -> PointFormat {
+        self.point_format
+    }
+
+    /// Set the format of points streamed via MMIO.
+    ///
+    /// Fails if the identified image does not support it; without an identified image,
+    /// only [`PointFormat::Padded`] is supported.
+    pub fn set_point_format(&mut self, point_format: PointFormat) -> Result<()> {
+        let supported = match self.image {
+            Some(image) => image.point_formats,
+            None => &[PointFormat::Padded],
         };
-        app.set_size();
-        app.set_first_bucket();
-        app.set_last_bucket();
-        app.set_ddr_read_len();
-        app.set_zero();
+        if !supported.contains(&point_format) {
+            return Err(Error::UnsupportedPointFormat(point_format));
+        }
+        self.point_format = point_format;
+        Ok(())
+    }
+
+    pub fn digit_width(&self) -> DigitWidth {
+        self.width
+    }
+
+    /// Set the digit width, which must match the bucket memory of the FPGA image.
+    ///
+    /// Resets the column range to all columns, the bucket range to all buckets,
+    /// and the bucket passes to one.
+    pub fn set_digit_width(&mut self, width: DigitWidth) {
+        self.width = width;
+        self.columns = 0..width.columns();
+        self.buckets = 0..width.buckets();
+        self.passes = 1;
+        self.set_bucket_registers(self.buckets.clone());
+    }
+
+    pub fn column_range(&self) -> Range<usize> {
+        self.columns.clone()
+    }
+
+    /// Restrict MSMs to the digit columns in `range`, least significant column first.
+    ///
+    /// The results of MSMs over disjoint column ranges add up to the full MSM, e.g. when
+    /// sharding columns across FPGAs.
+    pub fn set_column_range(&mut self, range: Range<usize>) -> Result<()> {
+        let columns = self.width.columns();
+        if range.start > range.end || range.end > columns {
+            return Err(Error::ColumnRangeOutOfBounds {
+                start: range.start,
+                end: range.end,
+                columns,
+            });
+        }
+        self.columns = range;
+        Ok(())
+    }
+
+    pub fn bucket_range(&self) -> Range<u32> {
+        self.buckets.clone()
+    }
+
+    /// Restrict MSMs to the buckets in `range`, ignoring digits of other magnitudes.
+    ///
+    /// Bucket `b` collects the digits `±(b + 1)`. Whether the results of MSMs over disjoint
+    /// bucket ranges add up to the full MSM depends on how the FPGA image weights the buckets
+    /// of a partial range; [`Sim`][crate::Sim] weights bucket `b` by `b + 1` in any range.
+    pub fn set_bucket_range(&mut self, range: Range<u32>) -> Result<()> {
+        let buckets = self.width.buckets();
+        if range.is_empty() || range.end > buckets {
+            return Err(Error::BucketRangeOutOfBounds {
+                start: range.start,
+                end: range.end,
+                buckets,
+            });
+        }
+        self.set_bucket_registers(range.clone());
+        // each pass needs at least one bucket
+        self.passes = self.passes.min(range.len());
+        self.buckets = range;
+        Ok(())
+    }
+
+    pub fn bucket_passes(&self) -> usize {
+        self.passes
+    }
+
+    /// Stream each column in `passes` passes over consecutive parts of the bucket range,
+    /// and add up their column sums on the host.
+    ///
+    /// This allows FPGA images with fewer buckets than the digit width needs, provided
+    /// they weight partial bucket ranges as described in [`App::set_bucket_range`].
+    pub fn set_bucket_passes(&mut self, passes: usize) -> Result<()> {
+        if passes == 0 || passes > self.buckets.len() {
+            return Err(Error::BucketPassesOutOfRange {
+                passes,
+                buckets: self.buckets.len() as u32,
+            });
+        }
+        self.passes = passes;
+        Ok(())
+    }
+
+    /// Part of the bucket range of the given pass.
+    fn pass_buckets(&self, pass: usize) -> Range<u32> {
+        let (start, len) = (self.buckets.start as usize, self.buckets.len());
+        let part = |pass: usize| (start + len * pass / self.passes) as u32;
+        part(pass)..part(pass + 1)
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    pub fn overlapped_readback(&self) -> bool {
+        self.overlapped_readback
+    }
+
+    /// Read back each column sum while the next column is streamed, instead of
+    /// before starting it.
+    ///
+    /// Off by default: this relies on the FPGA app keeping the sum in the coordinate
+    /// registers after the next column is started, which is only modelled by [`Sim`][crate::Sim].
+    pub fn set_overlapped_readback(&mut self, overlapped: bool) {
+        self.overlapped_readback = overlapped;
+    }
+
+    /// Activities of the last MSM, in order of completion.
+    pub fn timeline(&self) -> &[Span<Activity>] {
+        &self.timeline
+    }
 
-        app
+    /// Snapshot the FPGA state into a timeout error, and recover if so configured.
+    fn timeout(&mut self, waiting_for: Wait, offset: usize) -> Error {
+        let statistics = self.statistics();
+        if self.timeouts.recover {
+            self.initialize();
+        }
+        Error::Timeout {
+            waiting_for,
+            offset,
+            statistics,
+        }
     }
 
+    /// Stream all columns of the given instance, most significant first.
     #[inline]
-    fn column<'a>(
+    fn columns(&mut self, instance: usize, digits: &Digits, pipeline: &mut Pipeline) -> Result<()> {
+        debug_assert_eq!(digits.columns(), self.width.columns());
+        let result = self.column_passes(instance, digits, pipeline);
+        if self.passes > 1 {
+            // also after a failed column, so later MSMs see the full bucket range
+            self.set_bucket_registers(self.buckets.clone());
+        }
+        result
+    }
+
+    fn column_passes(
         &mut self,
-        i: usize,
-        scalars: impl Iterator<Item = &'a Scalar> + Clone + Send,
-        total: &mut G1TEProjective,
-    ) {
-        let mut cmds = Packet::default();
-        for j in (0..4).rev() {
-            timed(&format!("\n:: column {}", j as usize), || {
-                let mut stream = self.start_column();
-
-                let mut k = 0;
-                for scalar in scalars.clone() {
-                    let digit = single_digit_carry(scalar, i, j);
-                    cmds[k] = Command::set_digit(digit);
-                    k += 1;
-                    if k == 8 {
-                        stream.write(&cmds);
-                        k = 0;
-                    }
+        instance: usize,
+        digits: &Digits,
+        pipeline: &mut Pipeline,
+    ) -> Result<()> {
+        for column in self.columns.clone().rev() {
+            for pass in 0..self.passes {
+                if self.passes > 1 {
+                    let buckets = self.pass_buckets(pass);
+                    self.set_bucket_registers(buckets);
                 }
-                *total += timed("fetching point", || self.get_point());
-                if (i, j) != (0, 0) {
-                    shl_assign(total, 16);
+                let column = ColumnId {
+                    instance,
+                    column,
+                    pass,
+                };
+                timed(&format!("\n:: column {}", column.column), || {
+                    self.column(column, digits, pipeline)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stream a column, folding the column sums of the given instance.
+    ///
+    /// The sum of each column is read back once it is aggregated, or while the next column
+    /// is streamed if so configured, see [`App::set_overlapped_readback`].
+    #[inline]
+    fn column(&mut self, column: ColumnId, digits: &Digits, pipeline: &mut Pipeline) -> Result<()> {
+        let chunks = digits.column(column.column);
+        let mut readback = pipeline.pending.take().map(Readback::new);
+        if chunks.is_empty() {
+            // starting an empty column aggregates immediately
+            if let Some(readback) = readback.as_mut() {
+                readback.complete(&mut self.fpga);
+            }
+        }
+
+        let start = Instant::now();
+        let mut stream = self.start_column();
+
+        let mut cmds = Packet::default();
+        for (index, chunk) in chunks.iter().enumerate() {
+            for (cmd, digit) in cmds.iter_mut().zip(chunk.0) {
+                *cmd = Command::set_digit(digit);
+            }
+            let last = index + 1 == chunks.len();
+            if let Some(readback) = readback.as_mut() {
+                // the last packet leads to the next aggregation
+                match last {
+                    true => readback.complete(stream.fpga()),
+                    false => readback.step(stream.fpga()),
                 }
-            });
+            }
+            stream.write(&cmds);
+            if stream.backoff().expired() {
+                let offset = stream.offset() - Stream::Msm as usize;
+                return Err(self.timeout(Wait::DigitsQueue, offset));
+            }
         }
+        self.offset = stream.offset() - Stream::Msm as usize;
+        self.timeline
+            .push(Span::until_now(Activity::Stream(column), start));
+
+        if let Some(readback) = readback {
+            self.fold(pipeline, readback);
+        }
+        timed("waiting for aggregation", || self.wait_for_aggregation())?;
+        if self.overlapped_readback {
+            pipeline.pending = Some(column);
+        } else {
+            let mut readback = Readback::new(column);
+            readback.complete(&mut self.fpga);
+            self.fold(pipeline, readback);
+        }
+        Ok(())
+    }
+
+    /// Hand a completed column sum to the folding thread.
+    fn fold(&mut self, pipeline: &mut Pipeline, readback: Readback) {
+        let (column, (point, span)) = (readback.column, readback.finish());
+        self.timeline.push(span);
+        // the folding thread only exits once the pipeline is dropped
+        pipeline.points.send((column, point)).ok();
+    }
+
+    /// Read back the last column sum, and collect the totals of the given number of instances.
+    fn drain(&mut self, mut pipeline: Pipeline, instances: usize) -> Vec<G1TEProjective> {
+        if let Some(column) = pipeline.pending.take() {
+            let mut readback = Readback::new(column);
+            readback.complete(&mut self.fpga);
+            self.fold(&mut pipeline, readback);
+        }
+        let (mut totals, spans) = pipeline.finish();
+        self.timeline.extend(spans);
+        // instances without any column sums, e.g. for an empty column range
+        totals.resize(instances, G1TEProjective::zero());
+        totals
     }
 
     /// Perform full MSM.
     #[inline]
     pub fn msm<'a>(
         &mut self,
-        scalars: impl Iterator<Item = &'a Scalar> + Clone + ExactSizeIterator + Send,
-    ) -> G1Projective {
-        assert_eq!(scalars.len(), self.len as _);
-
-        let mut carried = self.carried.take().unwrap_or_else(|| unreachable!());
-
-        let mut total = G1TEProjective::zero();
-        let mut total0 = G1TEProjective::zero();
-        std::thread::scope(|s| {
-            s.spawn({
-                let scalars = scalars.clone();
-                || timed("limb carries", || limb_carries(scalars, &mut carried))
-            });
+        scalars: impl ExactSizeIterator<Item = &'a Scalar> + Clone + Send,
+    ) -> Result<G1Projective> {
+        self.check_len(scalars.len())?;
+        self.msm_range(0..self.len, scalars)
+    }
 
-            s.spawn(|| self.column(0, scalars, &mut total0));
-        });
+    /// Perform MSM against the first `scalars.len()` of the loaded points.
+    #[inline]
+    pub fn msm_prefix<'a>(
+        &mut self,
+        scalars: impl ExactSizeIterator<Item = &'a Scalar> + Clone + Send,
+    ) -> Result<G1Projective> {
+        self.msm_range(0..scalars.len(), scalars)
+    }
+
+    /// Perform MSM against the loaded points with indices in `range`.
+    ///
+    /// The FPGA app has no offset register, so digits for points before the range are
+    /// streamed as zeros, whereas the column ends early by programming the MSM length.
+    #[inline]
+    pub fn msm_range<'a>(
+        &mut self,
+        range: Range<usize>,
+        scalars: impl ExactSizeIterator<Item = &'a Scalar> + Clone + Send,
+    ) -> Result<G1Projective> {
+        Ok(into_weierstrass(&self.msm_te(range, scalars)?))
+    }
 
-        for i in (1..4).rev() {
-            self.column(i, carried.iter(), &mut total);
+    /// Like [`App::msm_range`], with the result in extended twisted Edwards coordinates.
+    pub(crate) fn msm_te<'a>(
+        &mut self,
+        range: Range<usize>,
+        scalars: impl ExactSizeIterator<Item = &'a Scalar> + Clone + Send,
+    ) -> Result<G1TEProjective> {
+        if range.start > range.end || range.end > self.len {
+            return Err(Error::RangeOutOfBounds {
+                start: range.start,
+                end: range.end,
+                len: self.len,
+            });
+        }
+        if scalars.len() != range.len() {
+            return Err(Error::LengthMismatch {
+                expected: range.len(),
+                actual: scalars.len(),
+            });
         }
 
-        shl_assign(&mut total, 48);
-        total += total0;
+        self.set_msm_length(range.end);
+        let mut digits = self.digits.take().unwrap_or_else(|| unreachable!());
+        // all digits of the zero scalars before the range are zero
+        let width = self.width;
+        timed("digits", || digits.compute(width, range.start, scalars));
+        let total = self.msm_digits(&digits);
+        self.digits = Some(digits);
+        self.set_msm_length(self.len);
 
-        let total = into_weierstrass(&total);
-        self.carried = Some(carried);
         total
     }
 
+    /// MSM of precomputed digits of the app's digit width, for the programmed MSM length.
+    #[inline]
+    pub(crate) fn msm_digits(&mut self, digits: &Digits) -> Result<G1TEProjective> {
+        self.timeline.clear();
+        let mut pipeline = Pipeline::new(digits.width(), self.passes, self.columns.start);
+        self.columns(0, digits, &mut pipeline)?;
+        Ok(self.drain(pipeline, 1)[0])
+    }
+
+    /// Perform full MSMs for several scalar vectors against the loaded points.
+    ///
+    /// The digits of each instance are calculated while the FPGA processes
+    /// the previous instance.
+    pub fn msm_batch(&mut self, instances: &[&[Scalar]]) -> Result<Vec<G1Projective>> {
+        for scalars in instances {
+            self.check_len(scalars.len())?;
+        }
+
+        let mut digits = self.digits.take().unwrap_or_else(|| unreachable!());
+        let mut next = core::mem::take(&mut self.next_digits);
+        let totals = self.msm_batch_digits(instances, &mut digits, &mut next);
+        self.digits = Some(digits);
+        self.next_digits = next;
+
+        Ok(totals?.iter().map(into_weierstrass).collect())
+    }
+
+    fn msm_batch_digits<'d>(
+        &mut self,
+        instances: &[&[Scalar]],
+        mut digits: &'d mut Digits,
+        mut next: &'d mut Digits,
+    ) -> Result<Vec<G1TEProjective>> {
+        self.timeline.clear();
+        let width = self.width;
+        let mut pipeline = Pipeline::new(width, self.passes, self.columns.start);
+
+        if let Some(first) = instances.first() {
+            timed("digits", || digits.compute(width, 0, first.iter()));
+        }
+
+        let following = instances.iter().skip(1).map(Some).chain(iter::once(None));
+        for (instance, following) in following.take(instances.len()).enumerate() {
+            // the following instance's digits are calculated while this instance is streamed
+            std::thread::scope(|s| {
+                if let Some(following) = following {
+                    s.spawn(|| timed("digits", || next.compute(width, 0, following.iter())));
+                }
+                self.columns(instance, digits, &mut pipeline)
+            })?;
+
+            core::mem::swap(&mut digits, &mut next);
+        }
+
+        Ok(self.drain(pipeline, instances.len()))
+    }
+
     /// Like `ark_ec::scalar_mul::variable_base::VariableBaseMSM::msm_bigint`
-    pub fn msm_bigint(&mut self, scalars: &[<Fr as ark_ff::PrimeField>::BigInt]) -> G1Projective {
+    pub fn msm_bigint(
+        &mut self,
+        scalars: &[<Fr as ark_ff::PrimeField>::BigInt],
+    ) -> Result<G1Projective> {
         self.msm(scalars.iter().map(|scalar| &scalar.0))
     }
 
@@ -197,11 +960,21 @@ impl App {
         self.len == 0
     }
 
+    fn check_len(&self, len: usize) -> Result<()> {
+        if len != self.len {
+            return Err(Error::LengthMismatch {
+                expected: self.len,
+                actual: len,
+            });
+        }
+        Ok(())
+    }
+
     fn set_zero(&mut self) {
         let zero = G1TEProjective::zero();
         let mut packet = Packet::default();
 
-        let mut stream: FpgaStream<'_, NullBackoff> = self.fpga.stream(Stream::SetZero as _);
+        let mut stream: FpgaStream<'_, F, NullBackoff> = self.fpga.stream(Stream::SetZero as _);
 
         packet[..6].copy_from_slice(zero.x.0.as_ref());
         stream.write(&packet);
@@ -216,18 +989,22 @@ impl App {
     }
 
     fn set_size(&mut self) {
-        self.fpga
-            .write(WriteRegister::MsmLength as _, &(self.len as u32));
+        self.set_msm_length(self.len);
     }
 
-    fn set_last_bucket(&mut self) {
+    /// Program the MSM length, rounded up to whole packets: columns are streamed as whole
+    /// packets, and the zero digits padding the last one add nothing.
+    fn set_msm_length(&mut self, len: usize) {
+        let len = (len + DIGITS_PER_PACKET - 1) / DIGITS_PER_PACKET * DIGITS_PER_PACKET;
         self.fpga
-            .write(WriteRegister::LastBucket as _, &LAST_BUCKET);
+            .write(WriteRegister::MsmLength as _, &(len as u32));
     }
 
-    fn set_first_bucket(&mut self) {
+    fn set_bucket_registers(&mut self, buckets: Range<u32>) {
+        self.fpga
+            .write(WriteRegister::FirstBucket as _, &buckets.start);
         self.fpga
-            .write(WriteRegister::FirstBucket as _, &FIRST_BUCKET);
+            .write(WriteRegister::LastBucket as _, &(buckets.end - 1));
     }
 
     fn set_ddr_read_len(&mut self) {
@@ -236,7 +1013,12 @@ impl App {
     }
 
     #[inline]
-    fn set_coordinates(&mut self, coordinate: Stream, coordinates: impl Iterator<Item = Fq>) {
+    fn set_coordinates(
+        &mut self,
+        coordinate: Stream,
+        first: usize,
+        coordinates: impl Iterator<Item = Fq>,
+    ) {
         debug_assert!([
             coordinate == Stream::SetX,
             coordinate == Stream::SetY,
@@ -245,77 +1027,211 @@ impl App {
         .iter()
         .any(|&condition| condition));
         let mut packet = Packet::default();
-        let mut stream: FpgaStream<'_, SetPointsBackoff> = self.fpga.stream(coordinate as _);
+        let mut stream: FpgaStream<'_, F, SetPointsBackoff> =
+            self.fpga.stream(coordinate as usize + first);
         for coordinate in coordinates {
             packet[..6].copy_from_slice(coordinate.0.as_ref());
             stream.write(&packet);
         }
     }
-    #[inline]
-    pub fn set_preprocessed_points(&mut self, points: &[G1PTEAffine]) {
-        assert!(self.len == points.len());
 
-        self.set_coordinates(Stream::SetX, points.iter().map(|point| point.x));
-        self.set_coordinates(Stream::SetY, points.iter().map(|point| point.y));
-        self.set_coordinates(Stream::SetKT, points.iter().map(|point| point.kt));
+    pub fn set_preprocessed_point_repeatedly(&mut self, point: &G1PTEAffine) -> Result<()> {
+        self.forget_points()?;
+        self.stream_points(0, iter::repeat(*point).take(self.len));
+        Ok(())
     }
 
-    pub fn set_points(&mut self, points: &[G1Affine]) {
-        assert!(self.len == points.len());
-        let preprocessed_points: Vec<_> = points.iter().map(|point| point.into()).collect();
-        self.set_preprocessed_points(&preprocessed_points);
+    /// State file recording the loaded points, by default keyed by the FPGA's slot.
+    pub fn state_file(&self) -> Option<&PathBuf> {
+        self.state_file.as_ref()
     }
 
-    pub fn set_preprocessed_point_repeatedly(&mut self, point: &G1PTEAffine) {
-        self.set_coordinates(Stream::SetX, iter::repeat(point.x).take(self.len));
-        self.set_coordinates(Stream::SetY, iter::repeat(point.y).take(self.len));
-        self.set_coordinates(Stream::SetKT, iter::repeat(point.kt).take(self.len));
+    /// Record the loaded points elsewhere, or not at all.
+    ///
+    /// Changing the points still removes the slot's state file, so no other process
+    /// attaches to stale points.
+    pub fn set_state_file(&mut self, state_file: Option<PathBuf>) {
+        self.state_file = state_file;
     }
 
-    #[cfg(feature = "hw")]
-    fn get_coordinate(&mut self, coordinate: ReadRegister) -> Fq {
-        debug_assert!([
-            coordinate == ReadRegister::X,
-            coordinate == ReadRegister::Y,
-            coordinate == ReadRegister::Z,
-            coordinate == ReadRegister::T,
-        ]
-        .iter()
-        .any(|&condition| condition));
-        let mut buffer = [0u64; 6];
-        for j in (0..12).step_by(2) {
-            self.fpga.write(WriteRegister::Query as _, &j);
-            let lo = self.fpga.read(coordinate as _);
+    /// Fingerprint of the loaded points, if known.
+    pub fn fingerprint(&self) -> Option<Fingerprint> {
+        self.fingerprint
+    }
+
+    /// Use the points loaded on the FPGA by an earlier process, instead of loading them.
+    ///
+    /// Fails unless the state file records points with the expected fingerprint, loaded
+    /// with the same image.
+    pub fn attach_preloaded(&mut self, expected: Fingerprint) -> Result<()> {
+        self.check_len(expected.len)?;
+        let state = match &self.state_file {
+            Some(path) => State::load(path)?,
+            None => None,
+        };
+        match state {
+            Some(state)
+                if state.fingerprint == expected
+                    && state.image_id.as_deref() == self.image.map(|image| image.id) =>
+            {
+                self.fingerprint = Some(expected);
+                Ok(())
+            }
+            state => Err(Error::PreloadedMismatch {
+                expected,
+                found: state.map(|state| state.fingerprint),
+            }),
+        }
+    }
+
+    /// Invalidate the state before changing the points, in case the change is interrupted.
+    fn forget_points(&mut self) -> Result<()> {
+        self.fingerprint = None;
+        // also if another state file is set, the slot's describes the same DDR
+        if let Some(slot) = self.slot {
+            State::remove(&state_file(slot))?;
+        }
+        match &self.state_file {
+            Some(path) => State::remove(path),
+            None => Ok(()),
+        }
+    }
 
-            self.fpga.write(WriteRegister::Query as _, &(j + 1));
-            let hi = self.fpga.read(coordinate as _);
+    fn remember_points(&mut self, fingerprint: Fingerprint) -> Result<()> {
+        self.fingerprint = Some(fingerprint);
+        match &self.state_file {
+            Some(path) => State {
+                image_id: self.image.map(|image| image.id.to_string()),
+                fingerprint,
+            }
+            .store(path),
+            None => Ok(()),
+        }
+    }
+
+    fn stream_points(&mut self, first: usize, points: impl Iterator<Item = G1PTEAffine> + Clone) {
+        match self.point_format {
+            PointFormat::Padded => {
+                self.set_coordinates(Stream::SetX, first, points.clone().map(|point| point.x));
+                self.set_coordinates(Stream::SetY, first, points.clone().map(|point| point.y));
+                self.set_coordinates(Stream::SetKT, first, points.map(|point| point.kt));
+            }
+            PointFormat::Packed => self.set_packed_points(first, points),
+        }
+    }
 
-            // | has lower precedence than <<, whereas + has higher
-            // and would need parentheses
-            buffer[j as usize / 2] = (hi as u64) << 32 | lo as u64;
+    /// Stream packed points, starting with a whole group at index `first`.
+    fn set_packed_points(&mut self, first: usize, points: impl Iterator<Item = G1PTEAffine>) {
+        debug_assert_eq!(first % PACKED_GROUP_POINTS, 0);
+        let mut packet = Packet::default();
+        let mut filled = 0;
+        let mut stream: FpgaStream<'_, F, SetPointsBackoff> = self.fpga.stream(
+            Stream::SetPoints as usize + first / PACKED_GROUP_POINTS * PACKED_GROUP_PACKETS,
+        );
+        for point in points {
+            for coordinate in [point.x, point.y, point.kt] {
+                for &limb in coordinate.0.as_ref() {
+                    packet[filled] = limb;
+                    filled += 1;
+                    if filled == packet.len() {
+                        stream.write(&packet);
+                        filled = 0;
+                    }
+                }
+            }
+        }
+        if filled > 0 {
+            packet[filled..].fill(0);
+            stream.write(&packet);
         }
-        ark_ff::BigInt(buffer).into()
     }
 
-    #[cfg(feature = "hw")]
-    pub fn get_point(&mut self) -> G1TEProjective {
+    /// Wait for the column sum to be aggregated.
+    ///
+    /// Note that [`fpga::Null`] never signals aggregation, so this only returns
+    /// if an aggregation timeout is set.
+    pub fn wait_for_aggregation(&mut self) -> Result<()> {
         self.fpga.flush();
+        let deadline = self
+            .timeouts
+            .aggregation
+            .map(|timeout| Instant::now() + timeout);
         while 0 == self.fpga.read(ReadRegister::Aggregated as _) {
-            continue;
+            if deadline.map_or(false, |deadline| Instant::now() > deadline) {
+                return Err(self.timeout(Wait::Aggregation, self.offset));
+            }
         }
+        Ok(())
+    }
 
-        let mut point = G1TEProjective::zero();
-        point.x = self.get_coordinate(ReadRegister::X);
-        point.y = self.get_coordinate(ReadRegister::Y);
-        point.z = self.get_coordinate(ReadRegister::Z);
-        point.t = self.get_coordinate(ReadRegister::T);
+    /// Wait for the column sum to be aggregated, and read it out.
+    pub fn get_point(&mut self) -> Result<G1TEProjective> {
+        self.wait_for_aggregation()?;
+        let mut readback = Readback::new(ColumnId::default());
+        readback.complete(&mut self.fpga);
+        Ok(readback.finish().0)
+    }
 
-        point
+    /// Spot-check that a random sample of the points is loaded on the FPGA, returning the
+    /// indices whose loaded point differs from `points`, cf. [`App::verify_point_indices`].
+    pub fn verify_points(&mut self, points: &[G1PTEAffine], samples: usize) -> Result<Vec<usize>> {
+        let mut rng = rand::thread_rng();
+        let mut indices =
+            rand::seq::index::sample(&mut rng, self.len, samples.min(self.len)).into_vec();
+        indices.sort_unstable();
+        self.verify_point_indices(points, indices)
     }
 
-    #[cfg(not(feature = "hw"))]
-    pub fn get_point(&mut self) -> G1TEProjective {
-        G1TEProjective::zero()
+    /// Check that the points at the given indices are loaded on the FPGA, returning the
+    /// indices whose loaded point differs from `points`.
+    ///
+    /// Each index is read back as the sum of a column with the single digit 1 at that index.
+    /// The column ends after the index, so small indices are cheaper.
+    pub fn verify_point_indices(
+        &mut self,
+        points: &[G1PTEAffine],
+        indices: impl IntoIterator<Item = usize>,
+    ) -> Result<Vec<usize>> {
+        self.check_len(points.len())?;
+        self.set_bucket_registers(0..self.width.buckets());
+        let mismatches = self.single_digit_columns(points, indices);
+        self.set_msm_length(self.len);
+        self.set_bucket_registers(self.buckets.clone());
+        mismatches
+    }
+
+    fn single_digit_columns(
+        &mut self,
+        points: &[G1PTEAffine],
+        indices: impl IntoIterator<Item = usize>,
+    ) -> Result<Vec<usize>> {
+        let mut zeros = Packet::default();
+        zeros.fill(Command::set_digit(0));
+        let mut mismatches = Vec::new();
+        for index in indices {
+            if index >= self.len {
+                return Err(Error::RangeOutOfBounds {
+                    start: index,
+                    end: index + 1,
+                    len: self.len,
+                });
+            }
+            self.set_msm_length(index + 1);
+
+            let mut last = zeros;
+            last[index % DIGITS_PER_PACKET] = Command::set_digit(1);
+            let mut stream = self.start_column();
+            for _ in 0..index / DIGITS_PER_PACKET {
+                stream.write(&zeros);
+            }
+            stream.write(&last);
+
+            let expected: G1TEProjective = G1TEAffine::from(&points[index]).into();
+            if self.get_point()? != expected {
+                mismatches.push(index);
+            }
+        }
+        Ok(mismatches)
     }
 
     pub fn statistics(&mut self) -> Statistics {
@@ -337,8 +1253,9 @@ impl App {
         self.fpga.read(ReadRegister::Statistic as _)
     }
 
-    pub fn start_column(&mut self) -> FpgaStream<'_, DigitsBackoff> {
-        let mut stream = self.fpga.stream(Stream::Msm as _);
+    pub fn start_column(&mut self) -> FpgaStream<'_, F, DigitsBackoff> {
+        let backoff = DigitsBackoff::new(self.timeouts.digits_queue);
+        let mut stream = self.fpga.stream_with(Stream::Msm as _, backoff);
 
         let mut packet = Packet::default();
         packet[0] = Command::StartColumn as _;
@@ -348,25 +1265,500 @@ impl App {
     }
 }
 
+#[derive(Copy, Clone, Debug, Default)]
 pub struct SetPointsBackoff;
-impl fpga::Backoff<Fpga> for SetPointsBackoff {
+impl<F: Flush> fpga::Backoff<F> for SetPointsBackoff {
     #[inline(always)]
-    fn backoff(fpga: &mut Fpga, offset: usize) {
+    fn backoff(&mut self, fpga: &mut F, offset: usize) {
         if (offset % SET_POINTS_FLUSH_EVERY) == 0 {
             fpga.flush();
         }
     }
 }
 
-pub struct DigitsBackoff;
-impl fpga::Backoff<Fpga> for DigitsBackoff {
+/// Waits for the FPGA's digits queue to drain, for at most the given timeout.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DigitsBackoff {
+    timeout: Option<Duration>,
+    expired: bool,
+}
+
+impl DigitsBackoff {
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            expired: false,
+        }
+    }
+
+    /// Whether the digits queue failed to drain in time; later backoffs no longer wait.
+    pub fn expired(&self) -> bool {
+        self.expired
+    }
+}
+
+impl<F: ReadWrite<u32>> fpga::Backoff<F> for DigitsBackoff {
     #[inline(always)]
-    fn backoff(fpga: &mut Fpga, offset: usize) {
-        if (offset % SET_DIGITS_FLUSH_BACKOFF_EVERY) == 0 {
+    fn backoff(&mut self, fpga: &mut F, offset: usize) {
+        if (offset % SET_DIGITS_FLUSH_BACKOFF_EVERY) == 0 && !self.expired {
             fpga.flush();
+            let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
             while fpga.read(ReadRegister::DigitsQueue as _) > BACKOFF_THRESHOLD {
-                continue;
+                if deadline.map_or(false, |deadline| Instant::now() > deadline) {
+                    self.expired = true;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        sim::{DDR_LAYOUT, SIM_IMAGE},
+        state::Fingerprint,
+        testing::harness_points,
+        Sim,
+    };
+    use ark_bls12_377::G1Projective;
+    use ark_ec::AffineRepr as _;
+    use ark_ff::PrimeField as _;
+    use ark_std::UniformRand as _;
+
+    /// App of `len` points on the simulator, and the points `beta^i * g` to load.
+    fn sim_app(sim: Sim, len: usize) -> (Fr, Vec<G1PTEAffine>, App<Sim>) {
+        let size = (usize::BITS - (len - 1).leading_zeros()) as u8;
+        let (beta, mut points) = harness_points(size);
+        points.truncate(len);
+        (beta, points, App::with_len(sim, len).unwrap())
+    }
+
+    /// Random scalars, and their MSM against the points `beta^(start + i) * g`.
+    fn instance(beta: &Fr, start: usize, len: usize) -> (Vec<Scalar>, G1Projective) {
+        let mut rng = rand::thread_rng();
+        let scalars: Vec<Fr> = (0..len).map(|_| Fr::rand(&mut rng)).collect();
+        let mut beta_i = ark_ff::Field::pow(beta, [start as u64]);
+        let mut prod = Fr::zero();
+        for &scalar in &scalars {
+            prod += scalar * beta_i;
+            beta_i *= beta;
+        }
+        let scalars = scalars
+            .iter()
+            .map(|scalar| scalar.into_bigint().0)
+            .collect();
+        (scalars, G1Affine::generator() * prod)
+    }
+
+    /// Check an MSM of random scalars against the first `len` loaded points `beta^i * g`.
+    fn assert_msm(app: &mut App<Sim>, beta: &Fr, len: usize) {
+        let (scalars, sum) = instance(beta, 0, len);
+        assert_eq!(app.msm(scalars.iter()).unwrap(), sum);
+    }
+
+    fn streams(app: &App<Sim>) -> impl Iterator<Item = &Span<Activity>> {
+        app.timeline()
+            .iter()
+            .filter(|span| matches!(span.activity, Activity::Stream(_)))
+    }
+
+    /// FPGA that never makes progress: all reads return `u32::MAX`.
+    #[derive(Default)]
+    struct Stuck {
+        // last bucket register
+        last_bucket: u32,
+    }
+
+    impl Flush for Stuck {
+        fn flush(&mut self) {}
+    }
+
+    impl Write<u32> for Stuck {
+        fn write(&mut self, index: usize, value: &u32) {
+            if index == WriteRegister::LastBucket as usize {
+                self.last_bucket = *value;
+            }
+        }
+    }
+
+    impl ReadWrite<u32> for Stuck {
+        fn read(&self, _: usize) -> u32 {
+            u32::MAX
+        }
+    }
+
+    impl Write<Packet> for Stuck {
+        fn write(&mut self, _: usize, _: &Packet) {}
+    }
+
+    impl Identify for Stuck {}
+
+    impl Images for Stuck {}
+
+    impl Dma for Stuck {}
+
+    #[test]
+    fn timeouts() {
+        let timeouts = Timeouts {
+            digits_queue: Some(Duration::from_millis(10)),
+            aggregation: Some(Duration::from_millis(10)),
+            recover: true,
+        };
+
+        let mut app = App::new(fpga::Null::new(), 4).unwrap();
+        app.set_timeouts(timeouts);
+        assert!(matches!(
+            app.get_point(),
+            Err(Error::Timeout {
+                waiting_for: Wait::Aggregation,
+                ..
+            })
+        ));
+
+        let size = 12;
+        let scalars = crate::testing::zero_scalars(size);
+        let mut app = App::new(Stuck::default(), size).unwrap();
+        app.set_timeouts(timeouts);
+        assert!(matches!(
+            app.msm(scalars.iter()),
+            Err(Error::Timeout {
+                waiting_for: Wait::DigitsQueue,
+                offset: SET_DIGITS_FLUSH_BACKOFF_EVERY,
+                statistics: Statistics {
+                    dropped_commands: u32::MAX,
+                    ..
+                },
+            })
+        ));
+        // the digits buffer survives the failed MSM
+        assert!(app.digits.is_some());
+
+        // the full bucket range is restored after a failed pass
+        app.set_timeouts(Timeouts {
+            recover: false,
+            ..timeouts
+        });
+        app.set_bucket_passes(2).unwrap();
+        assert!(app.msm(scalars.iter()).is_err());
+        assert_eq!(app.fpga.last_bucket, app.digit_width().buckets() - 1);
+    }
+
+    #[test]
+    fn msm_len() {
+        let len = 13;
+        let (beta, points, mut app) = sim_app(Sim::new(), len);
+        app.set_preprocessed_points(&points).unwrap();
+        assert_eq!(app.fpga.points().len(), len);
+
+        assert_msm(&mut app, &beta, len);
+        // the zero digits padding the last packet are within the MSM length
+        assert_eq!(app.statistics().dropped_commands, 0);
+    }
+
+    #[test]
+    fn msm_range() {
+        let (beta, points, mut app) = sim_app(Sim::new(), 16);
+        app.set_preprocessed_points(&points).unwrap();
+        for range in [0..5, 3..10, 7..16, 9..9] {
+            let (scalars, sum) = instance(&beta, range.start, range.len());
+            assert_eq!(app.msm_range(range, scalars.iter()).unwrap(), sum);
+        }
+
+        let (scalars, sum) = instance(&beta, 0, 11);
+        assert_eq!(app.msm_prefix(scalars.iter()).unwrap(), sum);
+
+        // the MSM length is restored afterwards
+        assert_msm(&mut app, &beta, 16);
+        assert_eq!(app.statistics().dropped_commands, 0);
+
+        assert!(matches!(
+            app.msm_range(10..17, scalars[..7].iter()),
+            Err(Error::RangeOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn msm_batch() {
+        let (beta, points, mut app) = sim_app(Sim::new(), 8);
+        app.set_preprocessed_points(&points).unwrap();
+        let instances: Vec<_> = (0..3).map(|_| instance(&beta, 0, 8)).collect();
+        let scalars: Vec<_> = instances
+            .iter()
+            .map(|(scalars, _)| scalars.as_slice())
+            .collect();
+
+        let results = app.msm_batch(&scalars).unwrap();
+        assert_eq!(results.len(), instances.len());
+        for (result, (_, sum)) in results.iter().zip(instances.iter()) {
+            assert_eq!(result, sum);
+        }
+        assert!(app.msm_batch(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn digit_width() {
+        let (beta, points, mut app) = sim_app(Sim::new(), 32);
+        app.set_preprocessed_points(&points).unwrap();
+        let (scalars, sum) = instance(&beta, 0, 32);
+        for bits in [12, 13, 16, 5] {
+            let width = DigitWidth::new(bits).unwrap();
+            app.set_digit_width(width);
+            assert_eq!(app.fpga.bucket_range(), 0..=width.buckets() - 1);
+
+            assert_eq!(app.msm(scalars.iter()).unwrap(), sum);
+            assert_eq!(streams(&app).count(), width.columns());
+        }
+    }
+
+    #[test]
+    fn bucket_ranges() {
+        let (beta, points, mut app) = sim_app(Sim::new(), 32);
+        app.set_preprocessed_points(&points).unwrap();
+        let (scalars, sum) = instance(&beta, 0, 32);
+
+        // partial MSMs over disjoint bucket ranges add up
+        let mut total = G1Projective::zero();
+        for range in [0..1, 1..1 << 14, 1 << 14..1 << 15] {
+            app.set_bucket_range(range.clone()).unwrap();
+            assert_eq!(app.fpga.bucket_range(), range.start..=range.end - 1);
+            total += app.msm(scalars.iter()).unwrap();
+        }
+        assert_eq!(total, sum);
+        assert!(app.set_bucket_range(0..(1 << 15) + 1).is_err());
+        assert!(app.set_bucket_range(3..3).is_err());
+
+        // passes over parts of the bucket range are merged on the host
+        app.set_bucket_range(0..1 << 15).unwrap();
+        for passes in [2, 3, 1] {
+            app.set_bucket_passes(passes).unwrap();
+            assert_eq!(app.msm(scalars.iter()).unwrap(), sum);
+            assert_eq!(app.fpga.bucket_range(), 0..=(1 << 15) - 1);
+            assert_eq!(streams(&app).count(), 16 * passes);
+        }
+        assert!(app.set_bucket_passes(0).is_err());
+
+        app.set_bucket_passes(4).unwrap();
+        app.set_bucket_range(5..8).unwrap();
+        assert_eq!(app.bucket_passes(), 3);
+    }
+
+    #[test]
+    fn pipeline() {
+        // enough packets per column to read back the previous column sum
+        let (beta, points, mut app) = sim_app(Sim::new(), 1 << 9);
+        app.set_preprocessed_points(&points).unwrap();
+        let (scalars, sum) = instance(&beta, 0, 1 << 9);
+        assert_eq!(app.msm(scalars.iter()).unwrap(), sum);
+        // by default, each column sum is read back before the next column starts
+        let readbacks = app
+            .timeline()
+            .iter()
+            .filter(|span| matches!(span.activity, Activity::Readback(_)));
+        for (readback, next) in readbacks.zip(streams(&app).skip(1)) {
+            assert!(readback.end <= next.start);
+        }
+
+        app.set_overlapped_readback(true);
+        assert_eq!(app.msm(scalars.iter()).unwrap(), sum);
+
+        let timeline = app.timeline();
+        let streams: Vec<_> = streams(&app).collect();
+        let readbacks: Vec<_> = timeline
+            .iter()
+            .filter(|span| matches!(span.activity, Activity::Readback(_)))
+            .collect();
+        let folds = timeline
+            .iter()
+            .filter(|span| matches!(span.activity, Activity::Fold(_)));
+        assert_eq!(streams.len(), 16);
+        assert_eq!(readbacks.len(), 16);
+        assert_eq!(folds.count(), 16);
+
+        // each column sum is read back while the next column is streamed
+        for ((readback, column), next) in readbacks.iter().zip(&streams).zip(&streams[1..]) {
+            match column.activity {
+                Activity::Stream(id) => assert_eq!(readback.activity, Activity::Readback(id)),
+                _ => unreachable!(),
+            }
+            assert!(readback.overlap(next) > Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn images() {
+        use crate::Sim;
+
+        assert!(App::new(Sim::new(), 4).is_ok());
+        assert!(App::new(fpga::Null::new(), 4).is_ok());
+
+        let mut sim = Sim::new();
+        sim.set_image_id(Some(ZPRIZE_IMAGE.to_string()));
+        assert!(matches!(
+            App::new(sim.clone(), 4),
+            Err(Error::IncompatibleImage { image, expected: IMAGE }) if image == ZPRIZE_IMAGE
+        ));
+        assert!(App::with_len_unchecked(sim, 16).is_ok());
+
+        struct Cleared;
+        impl Identify for Cleared {
+            fn image_id(&self) -> fpga::Result<Option<String>> {
+                Err(fpga::Error::NoImage(0))
+            }
+        }
+        impl Images for Cleared {}
+        assert!(matches!(
+            check_image(&Cleared),
+            Err(Error::Fpga(fpga::Error::NoImage(0)))
+        ));
+
+        // the simulated image is only supported by the simulator
+        struct Impostor;
+        impl Identify for Impostor {
+            fn image_id(&self) -> fpga::Result<Option<String>> {
+                Ok(Some(SIM_IMAGE.id.to_string()))
             }
         }
+        impl Images for Impostor {}
+        assert!(matches!(
+            check_image(&Impostor),
+            Err(Error::IncompatibleImage { .. })
+        ));
+    }
+
+    #[test]
+    fn dma_points() {
+        // only images declaring DMA use it
+        let mut sim = Sim::new();
+        sim.set_dma(true);
+        let (_, points, mut app) = sim_app(sim.clone(), 16);
+        app.set_preprocessed_points(&points).unwrap();
+        assert!(app.fpga.points() == &points[..]);
+        assert_eq!(app.fpga.dma_bytes(), 0);
+
+        sim.set_image_id(Some(SIM_IMAGE.id.to_string()));
+        let (beta, points, mut app) = sim_app(sim, 16);
+        app.set_preprocessed_points(&points).unwrap();
+        assert!(app.fpga.points() == &points[..]);
+        assert_eq!(app.fpga.dma_bytes(), 16 * DDR_LAYOUT.point_bytes());
+
+        let (scalars, sum) = instance(&beta, 0, 16);
+        assert_eq!(app.msm(scalars.iter()).unwrap(), sum);
+    }
+
+    #[test]
+    fn packed_points() {
+        // only images listing the format accept it
+        let (_, _, mut app) = sim_app(Sim::new(), 4);
+        assert!(matches!(
+            app.set_point_format(PointFormat::Packed),
+            Err(Error::UnsupportedPointFormat(PointFormat::Packed))
+        ));
+        assert_eq!(app.point_format(), PointFormat::Padded);
+
+        // 4 points per 9 packets, with partial last packets
+        for len in [1, 4, 7, 9, 16] {
+            let mut sim = Sim::new();
+            sim.set_image_id(Some(SIM_IMAGE.id.to_string()));
+            let (beta, points, mut app) = sim_app(sim, len);
+            assert_eq!(app.point_format(), PointFormat::Padded);
+            app.set_point_format(PointFormat::Packed).unwrap();
+            app.set_preprocessed_points(&points).unwrap();
+            // the padding of the last group lands beyond the points
+            assert!(app.fpga.points()[..len] == points[..]);
+
+            let (scalars, sum) = instance(&beta, 0, len);
+            assert_eq!(app.msm(scalars.iter()).unwrap(), sum);
+        }
+    }
+
+    #[test]
+    fn mapped_points() {
+        let (beta, points, mut app) = sim_app(Sim::new(), 16);
+        let name = std::env::temp_dir()
+            .join(format!("cyclone-mapped-{}.points", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        crate::io::write_points(&points, &name).unwrap();
+        let mapped = MappedPoints::open(&name).unwrap();
+
+        app.set_mapped_points(&mapped).unwrap();
+        assert!(app.fpga.points() == &points[..]);
+        assert_eq!(app.fingerprint(), Some(mapped.header().fingerprint()));
+
+        let (scalars, sum) = instance(&beta, 0, 16);
+        assert_eq!(app.msm(scalars.iter()).unwrap(), sum);
+
+        // corrupted point, detected after the upload
+        drop(mapped);
+        let mut bytes = std::fs::read(&name).unwrap();
+        bytes[crate::io::HEADER_LEN] ^= 1;
+        std::fs::write(&name, &bytes).unwrap();
+        let corrupted = MappedPoints::open(&name).unwrap();
+        assert!(app.set_mapped_points(&corrupted).is_err());
+        assert_eq!(app.fingerprint(), None);
+
+        std::fs::remove_file(name).unwrap();
+    }
+
+    #[test]
+    fn chunked_points() {
+        for (format, dma) in [
+            (PointFormat::Padded, false),
+            (PointFormat::Packed, false),
+            (PointFormat::Padded, true),
+        ] {
+            let mut sim = Sim::new();
+            sim.set_dma(dma);
+            sim.set_image_id(Some(SIM_IMAGE.id.to_string()));
+            let (beta, points, mut app) = sim_app(sim, 16);
+            app.set_point_format(format).unwrap();
+            // partial groups of packed points
+            let chunks = [&points[..3], &points[3..10], &points[10..]];
+            app.set_preprocessed_chunks(chunks.iter().map(|chunk| Ok(chunk.to_vec())))
+                .unwrap();
+            assert!(app.fpga.points() == &points[..]);
+            assert_eq!(app.fingerprint(), Some(Fingerprint::of(&points)));
+            assert_msm(&mut app, &beta, 16);
+
+            assert!(matches!(
+                app.set_preprocessed_chunks([Ok(points[1..].to_vec())]),
+                Err(Error::LengthMismatch { actual: 15, .. })
+            ));
+            assert!(matches!(
+                app.set_preprocessed_chunks([Ok(points.clone()), Ok(points[..1].to_vec())]),
+                Err(Error::LengthMismatch { actual: 17, .. })
+            ));
+            assert_eq!(app.fingerprint(), None);
+        }
+    }
+
+    #[test]
+    fn verify_points() {
+        let (beta, points, mut app) = sim_app(Sim::new(), 16);
+        app.set_bucket_range(1..7).unwrap();
+
+        let mut swapped = points.clone();
+        swapped.swap(3, 12);
+        app.set_preprocessed_points(&swapped).unwrap();
+        assert_eq!(app.verify_point_indices(&points, 0..16).unwrap(), [3, 12]);
+        assert_eq!(app.verify_points(&points, 100).unwrap(), [3, 12]);
+        assert!(app.verify_points(&swapped, 5).unwrap().is_empty());
+        // a column shorter than the MSM
+        assert!(app.verify_point_indices(&swapped, [2]).unwrap().is_empty());
+
+        // the bucket range and MSM length are restored for the next MSM
+        assert_eq!(app.fpga.bucket_range(), 1..=6);
+        let (_, _, mut reference) = sim_app(Sim::new(), 16);
+        reference.set_bucket_range(1..7).unwrap();
+        reference.set_preprocessed_points(&swapped).unwrap();
+        let (scalars, _) = instance(&beta, 0, 16);
+        assert_eq!(
+            app.msm(scalars.iter()).unwrap(),
+            reference.msm(scalars.iter()).unwrap()
+        );
+        assert_eq!(app.statistics().dropped_commands, 0);
     }
 }
@@ -1,10 +1,18 @@
+use std::time::Duration;
+
 use argh::FromArgs;
 use ark_bls12_377::Fr;
+use ark_serialize::Compress;
 
 use cyclone_msm::{
-    bls12_377::{into_weierstrass, G1PTEAffine},
+    app::Activity,
+    bls12_377::into_weierstrass,
     fpga,
-    io::{load, load_beta, load_points, load_slice, store, store_slice},
+    io::{
+        import_points, load, load_beta, map_points, migrate_raw_points, read_points,
+        read_points_header, store, write_points, SrsFormat, SrsReader,
+    },
+    preprocess::preprocess_chunks,
     testing::{harness_digits, harness_points, harness_scalars},
     timing::{always_timed, timed},
     App, Command, Packet,
@@ -21,7 +29,7 @@ pub struct Args {
     #[argh(positional)]
     pub name: String,
 
-    /// skip loading points
+    /// skip loading points, if the state file shows they are loaded
     #[argh(switch)]
     pub preloaded: bool,
 
@@ -29,6 +37,10 @@ pub struct Args {
     #[argh(switch, short = 'v')]
     pub verbose: bool,
 
+    /// read back column sums while streaming the next column
+    #[argh(switch)]
+    pub overlapped_readback: bool,
+
     #[argh(subcommand)]
     subcommand: Subcommand,
 }
@@ -41,6 +53,8 @@ enum Subcommand {
     Msm(Msm),
     Load(Load),
     Points(Points),
+    Migrate(Migrate),
+    Import(Import),
 }
 
 #[derive(FromArgs)]
@@ -56,25 +70,62 @@ struct Msm {}
 #[derive(FromArgs)]
 #[argh(subcommand, name = "load")]
 /// Load
-struct Load {}
+struct Load {
+    /// number of loaded points to spot-check
+    #[argh(option, default = "0")]
+    verify: usize,
+}
 
 #[derive(FromArgs)]
 #[argh(subcommand, name = "points")]
 /// Generate points
 struct Points {}
 
-fn main() {
+#[derive(FromArgs)]
+#[argh(subcommand, name = "migrate")]
+/// Convert a raw point file to the current point file format
+struct Migrate {
+    /// raw point file
+    #[argh(positional)]
+    raw: String,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "import")]
+/// Convert points serialized with arkworks to a point file
+struct Import {
+    /// serialized `Vec<G1Affine>`
+    #[argh(positional)]
+    srs: String,
+
+    /// read a KZG10 `Powers` instead, importing its `powers_of_g`
+    #[argh(switch)]
+    powers: bool,
+
+    /// read uncompressed points
+    #[argh(switch)]
+    uncompressed: bool,
+
+    /// load the points into the FPGA, instead of writing a point file
+    #[argh(switch)]
+    load: bool,
+}
+
+fn main() -> cyclone_msm::Result<()> {
     let args: Args = argh::from_env();
 
     match args.subcommand {
         Subcommand::Column(_) => {
-            let fpga = fpga().unwrap();
-            let mut app = App::new(fpga, args.size);
-            let beta = load_beta(&args.name);
+            let fpga = fpga()?;
+            let mut app = App::new(fpga, args.size)?;
+            let beta = load_beta(&args.name)?;
 
-            if !args.preloaded {
-                let points = load_points(args.size, &args.name);
-                always_timed("setting points", || app.set_preprocessed_points(&points));
+            if args.preloaded {
+                let header = read_points_header(&format!("{}.points", args.name))?;
+                app.attach_preloaded(header.fingerprint())?;
+            } else {
+                let points = map_points(args.size, &args.name)?;
+                always_timed("setting points", || app.set_mapped_points(&points))?;
             }
 
             if args.verbose {
@@ -97,34 +148,33 @@ fn main() {
                     stream.write(&packet);
                 }
 
-                let point = app.get_point();
-                into_weierstrass(&point)
-            });
+                let point = app.get_point()?;
+                Ok::<_, cyclone_msm::Error>(into_weierstrass(&point))
+            })?;
 
             if args.verbose {
                 println!("{:?}", app.statistics());
             }
 
-            if cfg!(feature = "hw") {
-                if point != sum {
-                    println!("\n==> FAILURE <==");
-                    std::process::exit(1);
-                } else {
-                    println!("\n==> SUCCESS <==");
-                }
+            if point != sum {
+                println!("\n==> FAILURE <==");
+                std::process::exit(1);
             } else {
-                println!("sum: {:?}", &sum);
-                println!("res: {:?}", &point);
+                println!("\n==> SUCCESS <==");
             }
         }
         Subcommand::Msm(_) => {
-            let fpga = fpga().unwrap();
-            let mut app = App::new(fpga, args.size);
-            let beta = load_beta(&args.name);
-
-            if !args.preloaded {
-                let points = load_points(args.size, &args.name);
-                always_timed("setting points", || app.set_preprocessed_points(&points));
+            let fpga = fpga()?;
+            let mut app = App::new(fpga, args.size)?;
+            app.set_overlapped_readback(args.overlapped_readback);
+            let beta = load_beta(&args.name)?;
+
+            if args.preloaded {
+                let header = read_points_header(&format!("{}.points", args.name))?;
+                app.attach_preloaded(header.fingerprint())?;
+            } else {
+                let points = map_points(args.size, &args.name)?;
+                always_timed("setting points", || app.set_mapped_points(&points))?;
             }
 
             if args.verbose {
@@ -133,7 +183,7 @@ fn main() {
 
             let (scalars, sum) =
                 always_timed("generating test case", || harness_scalars(&beta, args.size));
-            let point = always_timed(&format!("MSM/{}", args.size), || app.msm(scalars.iter()));
+            let point = always_timed(&format!("MSM/{}", args.size), || app.msm(scalars.iter()))?;
 
             // let (scalars, sum) =
             //     always_timed("generating test case", || cyclone_msm::testing::harness_bigints(&beta, args.size));
@@ -141,26 +191,47 @@ fn main() {
 
             if args.verbose {
                 println!("{:?}", app.statistics());
-            }
 
-            if cfg!(feature = "hw") {
-                if point != sum {
-                    println!("\n==> FAILURE <==");
-                    std::process::exit(1);
-                } else {
-                    println!("\n==> SUCCESS <==");
+                let timeline = app.timeline();
+                let streams = timeline
+                    .iter()
+                    .filter(|span| matches!(span.activity, Activity::Stream(_)));
+                let (mut readback, mut overlap) = (Duration::ZERO, Duration::ZERO);
+                for span in timeline {
+                    if let Activity::Readback(_) = span.activity {
+                        readback += span.duration();
+                        overlap += streams.clone().map(|stream| span.overlap(stream)).sum();
+                    }
                 }
+                println!(
+                    "read-back {:?}, of which {:?} during streaming",
+                    readback, overlap
+                );
+            }
+
+            if point != sum {
+                println!("\n==> FAILURE <==");
+                std::process::exit(1);
             } else {
-                println!("sum: {:?}", &sum);
-                println!("res: {:?}", &point);
+                println!("\n==> SUCCESS <==");
             }
         }
 
-        Subcommand::Load(_) => {
-            let fpga = fpga().unwrap();
-            let mut app = App::new(fpga, args.size);
-            let points = load_points(args.size, &args.name);
-            always_timed("setting points", || app.set_preprocessed_points(&points));
+        Subcommand::Load(load) => {
+            let fpga = fpga()?;
+            let mut app = App::new(fpga, args.size)?;
+            let points = map_points(args.size, &args.name)?;
+            always_timed("setting points", || app.set_mapped_points(&points))?;
+
+            if load.verify > 0 {
+                let mismatches = always_timed("verifying points", || {
+                    app.verify_points(points.points(), load.verify)
+                })?;
+                if !mismatches.is_empty() {
+                    println!("mismatching points at {:?}", mismatches);
+                    std::process::exit(1);
+                }
+            }
         }
 
         Subcommand::Points(_) => {
@@ -174,17 +245,49 @@ fn main() {
             let beta_name = format!("{}.beta", args.name);
             let points_name = format!("{}.points", args.name);
 
-            store(&beta, &beta_name);
+            store(&beta, &beta_name)?;
             let mut beta_load = Fr::default();
-            load(&mut beta_load, &beta_name);
+            load(&mut beta_load, &beta_name)?;
             println!("loaded beta {}", beta);
             assert_eq!(beta, beta_load);
 
-            store_slice(&points, &points_name);
-            let mut points_load = vec![G1PTEAffine::zero(); len];
-            timed("loading", || load_slice(&mut points_load, &points_name));
+            write_points(&points, &points_name)?;
+            let points_load = timed("loading", || read_points(&points_name))?;
+            assert_eq!(points_load.len(), len);
             let equal = points == points_load;
             assert!(equal);
         }
+
+        Subcommand::Migrate(migrate) => {
+            let points_name = format!("{}.points", args.name);
+            let len = always_timed("migrating points", || {
+                migrate_raw_points(&migrate.raw, &points_name)
+            })?;
+            println!("wrote {} points to {}", len, points_name);
+        }
+
+        Subcommand::Import(import) => {
+            let points_name = format!("{}.points", args.name);
+            let format = match import.powers {
+                false => SrsFormat::Points,
+                true => SrsFormat::KzgPowers,
+            };
+            let compress = match import.uncompressed {
+                false => Compress::Yes,
+                true => Compress::No,
+            };
+            if import.load {
+                let mut app = App::new(fpga()?, args.size)?;
+                let points = SrsReader::open(&import.srs, format, compress)?;
+                always_timed("importing points", || {
+                    app.set_preprocessed_chunks(preprocess_chunks(points))
+                })?;
+            } else {
+                let len = import_points(&import.srs, format, compress, &points_name)?;
+                println!("wrote {} points to {}", len, points_name);
+            }
+        }
     }
+
+    Ok(())
 }
@@ -1,61 +1,840 @@
 //! Load and store points efficiently.
+//!
+//! Points are stored in Cyclone point files: a header of [`HEADER_LEN`] bytes,
+//!
+//! | offset | field                                              |
+//! |--------|----------------------------------------------------|
+//! | 0      | magic bytes [`POINTS_MAGIC`]                       |
+//! | 8      | format version, [`POINTS_VERSION`]                 |
+//! | 12     | curve ID, [`CURVE_BLS12_377_G1`]                   |
+//! | 16     | flags: 1 = big-endian limbs, 2 = Montgomery form   |
+//! | 20     | bytes per point, [`POINT_LEN`]                     |
+//! | 24     | number of points                                   |
+//! | 32     | checksum, the hash of the points' [`Fingerprint`]  |
+//! | 40     | zero                                               |
+//!
+//! followed by X, Y and kT of each point, as six 64-bit limbs each, least significant first.
+//! Header fields are little-endian.
+//!
+//! Point files in the native layout (little-endian Montgomery form on little-endian hosts)
+//! can be memory-mapped with [`MappedPoints`], and streamed to the FPGA without copying.
+//!
+//! Point sets serialized with arkworks, e.g. the powers of tau of an SRS, are converted
+//! to point files with [`import_points`].
+//!
+//! Files written by [`store_slice`] before the header was introduced are raw dumps of
+//! `G1PTEAffine`s, which [`migrate_raw_points`] converts.
 
-use crate::{bls12_377::G1PTEAffine, timing::always_timed, Fr};
+use std::io::{BufReader, BufWriter, Read as _, Write as _};
 
-pub fn load_beta(name: &str) -> Fr {
+use ark_bls12_377::{Fq, G1Affine};
+use ark_ec::AffineRepr as _;
+use ark_ff::PrimeField as _;
+use ark_serialize::{
+    CanonicalDeserialize as _, CanonicalSerialize as _, Compress, SerializationError, Validate,
+};
+use ark_std::Zero as _;
+
+use crate::{
+    bls12_377::G1PTEAffine,
+    preprocess::preprocess_chunks,
+    state::{Fingerprint, FingerprintHasher},
+    timing::always_timed,
+    Error, Fr, Result,
+};
+
+/// Magic bytes of Cyclone point files.
+pub const POINTS_MAGIC: [u8; 8] = *b"CYCLONEP";
+/// Current version of the point file format.
+pub const POINTS_VERSION: u32 = 1;
+/// Curve ID of BLS12-377 G1, in the preprocessed twisted Edwards form of [`G1PTEAffine`].
+pub const CURVE_BLS12_377_G1: u32 = 1;
+/// Bytes of the point file header.
+pub const HEADER_LEN: usize = 64;
+/// Bytes per point in point files: three coordinates of six limbs.
+pub const POINT_LEN: usize = 3 * LIMBS * 8;
+
+const LIMBS: usize = 6;
+const FLAG_BIG_ENDIAN: u32 = 1;
+const FLAG_MONTGOMERY: u32 = 2;
+/// Points per read or write.
+const CHUNK_POINTS: usize = 1 << 14;
+
+pub fn load_beta(name: &str) -> Result<Fr> {
     let beta_name = format!("{}.beta", name);
     let mut beta = Fr::default();
-    load(&mut beta, &beta_name);
-    beta
+    load(&mut beta, &beta_name)?;
+    Ok(beta)
 }
 
-pub fn load_points(size: u8, name: &str) -> Vec<G1PTEAffine> {
+/// Load the `2^size` points of the point file `<name>.points`.
+///
+/// Cf. [`map_points`] to avoid holding a copy of the points in memory.
+pub fn load_points(size: u8, name: &str) -> Result<Vec<G1PTEAffine>> {
     let points_name = format!("{}.points", name);
-    let mut points = always_timed("allocating points", || vec![G1PTEAffine::zero(); 1 << size]);
-    always_timed("loading points", || load_slice(&mut points, &points_name));
-    points
+    let header = read_points_header(&points_name)?;
+    if header.len != 1 << size {
+        return Err(Error::LengthMismatch {
+            expected: 1 << size,
+            actual: header.len,
+        });
+    }
+    always_timed("loading points", || read_points(&points_name))
+}
+
+/// Memory-map the `2^size` points of the point file `<name>.points`.
+pub fn map_points(size: u8, name: &str) -> Result<MappedPoints> {
+    let points = MappedPoints::open(&format!("{}.points", name))?;
+    if points.len() != 1 << size {
+        return Err(Error::LengthMismatch {
+            expected: 1 << size,
+            actual: points.len(),
+        });
+    }
+    Ok(points)
+}
+
+/// Header of a point file.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PointsHeader {
+    pub version: u32,
+    pub curve: u32,
+    pub big_endian: bool,
+    pub montgomery: bool,
+    pub len: usize,
+    pub checksum: u64,
+}
+
+impl PointsHeader {
+    /// Header of the current format for the points.
+    pub fn new(points: &[G1PTEAffine]) -> Self {
+        PointsHeader {
+            version: POINTS_VERSION,
+            curve: CURVE_BLS12_377_G1,
+            big_endian: false,
+            montgomery: true,
+            len: points.len(),
+            checksum: Fingerprint::of(points).hash,
+        }
+    }
+
+    /// Fingerprint of the points, without reading them.
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint {
+            len: self.len,
+            hash: self.checksum,
+        }
+    }
+
+    fn encode(&self) -> [u8; HEADER_LEN] {
+        let flags =
+            (self.big_endian as u32 * FLAG_BIG_ENDIAN) | (self.montgomery as u32 * FLAG_MONTGOMERY);
+        let mut header = [0u8; HEADER_LEN];
+        header[..8].copy_from_slice(&POINTS_MAGIC);
+        header[8..12].copy_from_slice(&self.version.to_le_bytes());
+        header[12..16].copy_from_slice(&self.curve.to_le_bytes());
+        header[16..20].copy_from_slice(&flags.to_le_bytes());
+        header[20..24].copy_from_slice(&(POINT_LEN as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&(self.len as u64).to_le_bytes());
+        header[32..40].copy_from_slice(&self.checksum.to_le_bytes());
+        header
+    }
+
+    fn decode(name: &str, header: &[u8; HEADER_LEN]) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidPointFile {
+            name: name.to_string(),
+            reason,
+        };
+        let u32_at = |offset: usize| u32::from_le_bytes(header[offset..][..4].try_into().unwrap());
+        let u64_at = |offset: usize| u64::from_le_bytes(header[offset..][..8].try_into().unwrap());
+
+        if header[..8] != POINTS_MAGIC {
+            return Err(invalid(
+                "no Cyclone point file header, raw point files can be converted with `migrate_raw_points`"
+                    .to_string(),
+            ));
+        }
+        let version = u32_at(8);
+        if version != POINTS_VERSION {
+            return Err(invalid(format!(
+                "format version {} is not supported, expected {}",
+                version, POINTS_VERSION
+            )));
+        }
+        let curve = u32_at(12);
+        if curve != CURVE_BLS12_377_G1 {
+            return Err(invalid(format!("curve ID {} is not BLS12-377 G1", curve)));
+        }
+        let flags = u32_at(16);
+        if flags & !(FLAG_BIG_ENDIAN | FLAG_MONTGOMERY) != 0 {
+            return Err(invalid(format!("unknown flags {:#x}", flags)));
+        }
+        let point_len = u32_at(20) as usize;
+        if point_len != POINT_LEN {
+            return Err(invalid(format!(
+                "{}B per point, expected {}B",
+                point_len, POINT_LEN
+            )));
+        }
+        Ok(PointsHeader {
+            version,
+            curve,
+            big_endian: flags & FLAG_BIG_ENDIAN != 0,
+            montgomery: flags & FLAG_MONTGOMERY != 0,
+            len: u64_at(24) as usize,
+            checksum: u64_at(32),
+        })
+    }
+}
+
+/// Size of a point file of `len` points, failing if it overflows.
+fn file_size(name: &str, len: usize) -> Result<usize> {
+    len.checked_mul(POINT_LEN)
+        .and_then(|size| size.checked_add(HEADER_LEN))
+        .ok_or_else(|| Error::InvalidPointFile {
+            name: name.to_string(),
+            reason: format!("{} points overflow the file size", len),
+        })
+}
+
+fn open_points(name: &str) -> Result<(PointsHeader, std::fs::File)> {
+    let mut file = std::fs::File::open(name).map_err(io_error(name))?;
+    let actual = file.metadata().map_err(io_error(name))?.len();
+    let mut header = [0u8; HEADER_LEN];
+    if actual >= HEADER_LEN as u64 {
+        file.read_exact(&mut header).map_err(io_error(name))?;
+    }
+    let header = PointsHeader::decode(name, &header)?;
+    let expected = file_size(name, header.len)? as u64;
+    if actual != expected {
+        return Err(Error::CorruptedFile {
+            name: name.to_string(),
+            expected,
+            actual,
+        });
+    }
+    Ok((header, file))
+}
+
+/// Read the header of a point file, checking the file size.
+pub fn read_points_header(name: &str) -> Result<PointsHeader> {
+    Ok(open_points(name)?.0)
+}
+
+fn decode_coordinate(header: &PointsHeader, bytes: &[u8]) -> Option<Fq> {
+    let mut limbs = [0u64; LIMBS];
+    for (limb, bytes) in limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+        let bytes = bytes.try_into().unwrap();
+        *limb = match header.big_endian {
+            false => u64::from_le_bytes(bytes),
+            true => u64::from_be_bytes(bytes),
+        };
+    }
+    if header.montgomery {
+        let mut coordinate = Fq::zero();
+        coordinate.0 .0 = limbs;
+        Some(coordinate)
+    } else {
+        Fq::from_bigint(ark_ff::BigInt(limbs))
+    }
+}
+
+/// Read all points of a point file, verifying their checksum.
+pub fn read_points(name: &str) -> Result<Vec<G1PTEAffine>> {
+    let (header, file) = open_points(name)?;
+    let invalid = |reason: String| Error::InvalidPointFile {
+        name: name.to_string(),
+        reason,
+    };
+
+    let mut reader = BufReader::new(file);
+    let mut points = Vec::with_capacity(header.len);
+    let mut buffer = vec![0u8; CHUNK_POINTS * POINT_LEN];
+    while points.len() < header.len {
+        let chunk = CHUNK_POINTS.min(header.len - points.len());
+        let bytes = &mut buffer[..chunk * POINT_LEN];
+        reader.read_exact(bytes).map_err(io_error(name))?;
+        for bytes in bytes.chunks_exact(POINT_LEN) {
+            let mut coordinates = bytes
+                .chunks_exact(LIMBS * 8)
+                .map(|bytes| decode_coordinate(&header, bytes));
+            let mut next = || {
+                coordinates.next().unwrap().ok_or_else(|| {
+                    invalid(format!(
+                        "coordinate of point {} exceeds the modulus",
+                        points.len()
+                    ))
+                })
+            };
+            let (x, y, kt) = (next()?, next()?, next()?);
+            points.push(G1PTEAffine { x, y, kt });
+        }
+    }
+
+    if Fingerprint::of(&points).hash != header.checksum {
+        return Err(invalid("checksum mismatch".to_string()));
+    }
+    Ok(points)
 }
 
-pub fn store_slice<T: Sized>(slice: &[T], name: &str) {
+/// Write the points to a point file of the current format.
+pub fn write_points(points: &[G1PTEAffine], name: &str) -> Result<()> {
+    let header = PointsHeader::new(points);
+    let file = std::fs::File::create(name).map_err(io_error(name))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&header.encode()).map_err(io_error(name))?;
+
+    let mut buffer = vec![0u8; CHUNK_POINTS.min(points.len()) * POINT_LEN];
+    encode_points(points, &mut buffer, &mut writer).map_err(io_error(name))?;
+    writer.flush().map_err(io_error(name))
+}
+
+fn encode_points(
+    points: &[G1PTEAffine],
+    buffer: &mut [u8],
+    writer: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    for chunk in points.chunks(CHUNK_POINTS) {
+        let bytes = &mut buffer[..chunk.len() * POINT_LEN];
+        for (point, bytes) in chunk.iter().zip(bytes.chunks_exact_mut(POINT_LEN)) {
+            let limbs = [point.x, point.y, point.kt]
+                .into_iter()
+                .flat_map(|coordinate| coordinate.0 .0);
+            for (limb, bytes) in limbs.zip(bytes.chunks_exact_mut(8)) {
+                bytes.copy_from_slice(&limb.to_le_bytes());
+            }
+        }
+        writer.write_all(bytes)?;
+    }
+    Ok(())
+}
+
+/// Point file written piecewise, e.g. while preprocessing.
+///
+/// The header is written by [`PointsWriter::finish`], until then the file is invalid.
+#[derive(Debug)]
+pub struct PointsWriter {
+    name: String,
+    writer: BufWriter<std::fs::File>,
+    hasher: FingerprintHasher,
+    buffer: Vec<u8>,
+}
+
+impl PointsWriter {
+    pub fn create(name: &str) -> Result<Self> {
+        let file = std::fs::File::create(name).map_err(io_error(name))?;
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(&[0u8; HEADER_LEN])
+            .map_err(io_error(name))?;
+        Ok(PointsWriter {
+            name: name.to_string(),
+            writer,
+            hasher: FingerprintHasher::new(),
+            buffer: vec![0u8; CHUNK_POINTS * POINT_LEN],
+        })
+    }
+
+    /// Append the points.
+    pub fn write(&mut self, points: &[G1PTEAffine]) -> Result<()> {
+        self.hasher.update(points);
+        encode_points(points, &mut self.buffer, &mut self.writer).map_err(io_error(&self.name))
+    }
+
+    /// Write the header, returning it.
+    pub fn finish(self) -> Result<PointsHeader> {
+        use std::io::{Seek as _, SeekFrom};
+
+        let name = &self.name;
+        let fingerprint = self.hasher.finish();
+        let header = PointsHeader {
+            len: fingerprint.len,
+            checksum: fingerprint.hash,
+            ..PointsHeader::new(&[])
+        };
+        let mut file = self
+            .writer
+            .into_inner()
+            .map_err(|err| io_error(name)(err.into_error()))?;
+        file.seek(SeekFrom::Start(0)).map_err(io_error(name))?;
+        file.write_all(&header.encode()).map_err(io_error(name))?;
+        Ok(header)
+    }
+}
+
+/// Convert a raw point file, as written by [`store_slice`], to the current format.
+///
+/// Returns the number of points.
+pub fn migrate_raw_points(raw: &str, name: &str) -> Result<usize> {
+    let actual = std::fs::metadata(raw).map_err(io_error(raw))?.len();
+    let point_size = std::mem::size_of::<G1PTEAffine>() as u64;
+    if actual % point_size != 0 {
+        return Err(Error::CorruptedFile {
+            name: raw.to_string(),
+            expected: actual / point_size * point_size,
+            actual,
+        });
+    }
+    let mut points = vec![G1PTEAffine::zero(); (actual / point_size) as usize];
+    load_slice(&mut points, raw)?;
+    write_points(&points, name)?;
+    Ok(points.len())
+}
+
+// the points of mapped files are cast to `G1PTEAffine`s
+const _: () = assert!(core::mem::size_of::<G1PTEAffine>() == POINT_LEN);
+
+/// Point file mapped into memory, read-only.
+///
+/// The points are paged in as they are accessed, so uploading them with
+/// [`App::set_mapped_points`][crate::App::set_mapped_points] starts immediately,
+/// and the page cache holds the only copy.
+#[derive(Debug)]
+pub struct MappedPoints {
+    name: String,
+    header: PointsHeader,
+    address: *mut libc::c_void,
+    size: usize,
+}
+
+// the mapping is read-only, and unmapped only on drop
+unsafe impl Send for MappedPoints {}
+unsafe impl Sync for MappedPoints {}
+
+impl MappedPoints {
+    /// Map a point file, which must be in the native layout.
+    ///
+    /// Only the header is read, the checksum is checked by [`MappedPoints::verify`].
+    pub fn open(name: &str) -> Result<Self> {
+        use std::os::unix::io::AsRawFd as _;
+
+        let (header, file) = open_points(name)?;
+        if header.big_endian || !header.montgomery || cfg!(target_endian = "big") {
+            return Err(Error::InvalidPointFile {
+                name: name.to_string(),
+                reason: "points are not in the native layout, read them with `read_points`"
+                    .to_string(),
+            });
+        }
+        let size = file_size(name, header.len)?;
+        let address = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                size,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if address == libc::MAP_FAILED {
+            return Err(io_error(name)(std::io::Error::last_os_error()));
+        }
+        // start reading ahead, so that the file pages in while the first points are used;
+        // merely advisory, so failures are ignored
+        unsafe { libc::madvise(address, size, libc::MADV_WILLNEED) };
+        Ok(MappedPoints {
+            name: name.to_string(),
+            header,
+            address,
+            size,
+        })
+    }
+
+    pub fn header(&self) -> &PointsHeader {
+        &self.header
+    }
+
+    pub fn len(&self) -> usize {
+        self.header.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.len == 0
+    }
+
+    /// The mapped points, paged in on access.
+    pub fn points(&self) -> &[G1PTEAffine] {
+        // mappings are page-aligned, so the points are aligned after the header
+        unsafe {
+            core::slice::from_raw_parts(
+                (self.address as *const u8).add(HEADER_LEN) as *const G1PTEAffine,
+                self.header.len,
+            )
+        }
+    }
+
+    /// Check the points against the header's checksum, returning their fingerprint.
+    pub fn verify(&self) -> Result<Fingerprint> {
+        let fingerprint = Fingerprint::of(self.points());
+        if fingerprint != self.header.fingerprint() {
+            return Err(Error::InvalidPointFile {
+                name: self.name.clone(),
+                reason: "checksum mismatch".to_string(),
+            });
+        }
+        Ok(fingerprint)
+    }
+}
+
+impl Drop for MappedPoints {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.address, self.size) };
+    }
+}
+
+/// Serialization of imported point sets.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SrsFormat {
+    /// `Vec<G1Affine>`
+    Points,
+    /// KZG10 `Powers<Bls12_377>`, of which the `powers_of_g` are imported
+    KzgPowers,
+}
+
+/// Streaming deserialization of points serialized with arkworks' `CanonicalSerialize`,
+/// checking that they are in the prime-order subgroup.
+///
+/// Trailing data is rejected, once all points are read.
+pub struct SrsReader<R> {
+    reader: R,
+    name: String,
+    format: SrsFormat,
+    compress: Compress,
+    len: usize,
+    remaining: usize,
+    done: bool,
+}
+
+impl SrsReader<BufReader<std::fs::File>> {
+    pub fn open(srs: &str, format: SrsFormat, compress: Compress) -> Result<Self> {
+        let file = std::fs::File::open(srs).map_err(io_error(srs))?;
+        Self::new(BufReader::new(file), srs, format, compress)
+    }
+}
+
+impl<R: std::io::Read> SrsReader<R> {
+    /// Read the number of points, `name` is used in errors.
+    pub fn new(mut reader: R, name: &str, format: SrsFormat, compress: Compress) -> Result<Self> {
+        let len = u64::deserialize_with_mode(&mut reader, compress, Validate::No)
+            .map_err(invalid_srs(name))? as usize;
+        Ok(SrsReader {
+            reader,
+            name: name.to_string(),
+            format,
+            compress,
+            len,
+            remaining: len,
+            done: false,
+        })
+    }
+
+    /// Number of points.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn finish(&mut self) -> core::result::Result<(), SerializationError> {
+        if self.format == SrsFormat::KzgPowers {
+            // powers_of_gamma_g, not imported
+            let len = u64::deserialize_with_mode(&mut self.reader, self.compress, Validate::No)?;
+            let bytes = len
+                .checked_mul(G1Affine::zero().serialized_size(self.compress) as u64)
+                .ok_or(SerializationError::InvalidData)?;
+            let skipped =
+                std::io::copy(&mut self.reader.by_ref().take(bytes), &mut std::io::sink())?;
+            if skipped != bytes {
+                return Err(SerializationError::InvalidData);
+            }
+        }
+        if self.reader.read(&mut [0u8])? != 0 {
+            return Err(SerializationError::InvalidData);
+        }
+        Ok(())
+    }
+}
+
+impl<R: std::io::Read> Iterator for SrsReader<R> {
+    type Item = Result<G1Affine>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let point = match self.remaining {
+            0 => self.finish().map(|()| None),
+            // validation checks the curve equation and the subgroup
+            _ => G1Affine::deserialize_with_mode(&mut self.reader, self.compress, Validate::Yes)
+                .map(Some),
+        };
+        self.remaining = self.remaining.saturating_sub(1);
+        match point {
+            Ok(Some(point)) => Some(Ok(point)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(invalid_srs(&self.name)(err)))
+            }
+        }
+    }
+}
+
+/// Import points serialized with arkworks from the file `srs`, and write them
+/// preprocessed to the point file `name`.
+///
+/// Preprocesses chunk by chunk, so memory use does not depend on the number of points.
+/// Returns the number of points.
+pub fn import_points(
+    srs: &str,
+    format: SrsFormat,
+    compress: Compress,
+    name: &str,
+) -> Result<usize> {
+    let points = SrsReader::open(srs, format, compress)?;
+    let mut writer = PointsWriter::create(name)?;
+    always_timed("importing points", || {
+        for chunk in preprocess_chunks(points) {
+            writer.write(&chunk?)?;
+        }
+        Ok(writer.finish()?.len)
+    })
+}
+
+fn invalid_srs(name: &str) -> impl FnOnce(SerializationError) -> Error + '_ {
+    move |source| Error::InvalidSrs {
+        name: name.to_string(),
+        source,
+    }
+}
+
+fn io_error(name: &str) -> impl FnOnce(std::io::Error) -> Error + '_ {
+    move |source| Error::Io {
+        name: name.to_string(),
+        source,
+    }
+}
+
+/// Open a file for reading, checking that it contains exactly `size` bytes.
+fn open_exact(name: &str, size: usize) -> Result<std::fs::File> {
+    let file = std::fs::File::open(name).map_err(io_error(name))?;
+    let actual = file.metadata().map_err(io_error(name))?.len();
+    if actual != size as u64 {
+        return Err(Error::CorruptedFile {
+            name: name.to_string(),
+            expected: size as u64,
+            actual,
+        });
+    }
+    Ok(file)
+}
+
+pub fn store_slice<T: Sized>(slice: &[T], name: &str) -> Result<()> {
     use std::io::Write as _;
-    let slice_data_size = std::mem::size_of::<T>() * slice.len();
+    let slice_data_size = std::mem::size_of_val(slice);
     std::fs::File::create(name)
-        .unwrap()
+        .map_err(io_error(name))?
         .write_all(unsafe {
             std::slice::from_raw_parts(slice.as_ptr() as *const u8, slice_data_size)
         })
-        .unwrap();
+        .map_err(io_error(name))?;
     println!("store {}B to {}", slice_data_size, name);
+    Ok(())
 }
 
-pub fn load_slice<T: Sized>(slice: &mut [T], name: &str) {
+pub fn load_slice<T: Sized>(slice: &mut [T], name: &str) -> Result<()> {
     use std::io::Read as _;
-    let slice_data_size = std::mem::size_of::<T>() * slice.len();
-    std::fs::File::open(name)
-        .unwrap()
+    let slice_data_size = std::mem::size_of_val(slice);
+    open_exact(name, slice_data_size)?
         .read_exact(unsafe {
             std::slice::from_raw_parts_mut(slice.as_mut_ptr() as *mut u8, slice_data_size)
         })
-        .unwrap();
+        .map_err(io_error(name))?;
     println!("load {}B from {}", slice_data_size, name);
+    Ok(())
 }
-pub fn store<T: Sized>(data: &T, name: &str) {
+pub fn store<T: Sized>(data: &T, name: &str) -> Result<()> {
     use std::io::Write as _;
     let size = std::mem::size_of::<T>();
     std::fs::File::create(name)
-        .unwrap()
+        .map_err(io_error(name))?
         .write_all(unsafe { std::slice::from_raw_parts(data as *const T as *const u8, size) })
-        .unwrap();
+        .map_err(io_error(name))?;
     println!("store {}B to {}", size, name);
+    Ok(())
 }
 
-pub fn load<T: Sized>(data: &mut T, name: &str) {
+pub fn load<T: Sized>(data: &mut T, name: &str) -> Result<()> {
     use std::io::Read as _;
     let size = std::mem::size_of::<T>();
     println!("name: {}", name);
-    std::fs::File::open(name)
-        .unwrap_or_else(|_| panic!("no such file {}", name))
+    open_exact(name, size)?
         .read_exact(unsafe { std::slice::from_raw_parts_mut(data as *mut T as *mut u8, size) })
-        .unwrap();
+        .map_err(io_error(name))?;
     println!("load {}B from {}", size, name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn point_files() {
+        let name = std::env::temp_dir()
+            .join(format!("cyclone-points-{}", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        let points_name = format!("{}.points", name);
+        let raw_name = format!("{}.raw", name);
+        let (_, points) = crate::testing::harness_points(3);
+
+        write_points(&points, &points_name).unwrap();
+        let header = read_points_header(&points_name).unwrap();
+        assert_eq!(header.fingerprint(), Fingerprint::of(&points));
+        assert!(matches!(load_points(3, &name), Ok(loaded) if loaded == points));
+        assert!(matches!(
+            load_points(2, &name),
+            Err(Error::LengthMismatch {
+                expected: 4,
+                actual: 8
+            })
+        ));
+
+        // corrupted point
+        let mut bytes = std::fs::read(&points_name).unwrap();
+        bytes[HEADER_LEN + POINT_LEN + 3] ^= 1;
+        std::fs::write(&points_name, &bytes).unwrap();
+        assert!(matches!(
+            read_points(&points_name),
+            Err(Error::InvalidPointFile { reason, .. }) if reason == "checksum mismatch"
+        ));
+
+        // truncated file
+        std::fs::write(&points_name, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(
+            read_points(&points_name),
+            Err(Error::CorruptedFile { expected, actual, .. }) if expected == actual + 1
+        ));
+
+        // length overflowing the file size
+        let mut overflowing = bytes.clone();
+        overflowing[24..32].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&points_name, &overflowing).unwrap();
+        assert!(matches!(
+            read_points(&points_name),
+            Err(Error::InvalidPointFile { reason, .. }) if reason.ends_with("overflow the file size")
+        ));
+
+        // other version
+        bytes[8] = 2;
+        std::fs::write(&points_name, &bytes).unwrap();
+        assert!(matches!(
+            read_points(&points_name),
+            Err(Error::InvalidPointFile { reason, .. }) if reason.starts_with("format version 2")
+        ));
+
+        // raw files are rejected, and can be migrated
+        store_slice(&points, &raw_name).unwrap();
+        assert!(matches!(
+            read_points(&raw_name),
+            Err(Error::InvalidPointFile { .. })
+        ));
+        assert_eq!(migrate_raw_points(&raw_name, &points_name).unwrap(), 8);
+        assert!(matches!(load_points(3, &name), Ok(loaded) if loaded == points));
+
+        let mapped = map_points(3, &name).unwrap();
+        assert!(mapped.points() == points);
+        assert_eq!(mapped.verify().unwrap(), Fingerprint::of(&points));
+        assert!(matches!(
+            map_points(2, &name),
+            Err(Error::LengthMismatch { .. })
+        ));
+
+        assert!(matches!(load_beta(&name), Err(Error::Io { .. })));
+
+        std::fs::remove_file(points_name).unwrap();
+        std::fs::remove_file(raw_name).unwrap();
+    }
+
+    #[test]
+    fn import() {
+        let name = std::env::temp_dir()
+            .join(format!("cyclone-import-{}", std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string();
+        let points_name = format!("{}.points", name);
+        let srs_name = format!("{}.srs", name);
+        let points = crate::testing::random_points(3);
+        let preprocessed = crate::preprocess::preprocess_points(&points);
+
+        for compress in [Compress::Yes, Compress::No] {
+            for format in [SrsFormat::Points, SrsFormat::KzgPowers] {
+                let mut srs = vec![];
+                points.serialize_with_mode(&mut srs, compress).unwrap();
+                if format == SrsFormat::KzgPowers {
+                    points[..2].serialize_with_mode(&mut srs, compress).unwrap();
+                }
+                std::fs::write(&srs_name, &srs).unwrap();
+                assert_eq!(
+                    import_points(&srs_name, format, compress, &points_name).unwrap(),
+                    8
+                );
+                assert!(read_points(&points_name).unwrap() == preprocessed);
+
+                // trailing data
+                srs.push(0);
+                std::fs::write(&srs_name, &srs).unwrap();
+                assert!(matches!(
+                    import_points(&srs_name, format, compress, &points_name),
+                    Err(Error::InvalidSrs { .. })
+                ));
+            }
+        }
+
+        // on the curve, but outside the subgroup
+        let outside = (1u64..)
+            .filter_map(|x| G1Affine::get_point_from_x_unchecked(Fq::from(x), false))
+            .find(|point| !point.is_in_correct_subgroup_assuming_on_curve())
+            .unwrap();
+        let mut srs = vec![];
+        vec![points[0], outside]
+            .serialize_uncompressed(&mut srs)
+            .unwrap();
+        let mut reader = SrsReader::new(&srs[..], "srs", SrsFormat::Points, Compress::No).unwrap();
+        assert_eq!(reader.len(), 2);
+        assert!(matches!(reader.next(), Some(Ok(point)) if point == points[0]));
+        assert!(matches!(
+            reader.next(),
+            Some(Err(Error::InvalidSrs {
+                source: SerializationError::InvalidData,
+                ..
+            }))
+        ));
+        assert!(reader.next().is_none());
+
+        // overflowing length of the skipped powers of gamma
+        let mut srs = vec![];
+        points.serialize_compressed(&mut srs).unwrap();
+        u64::MAX.serialize_compressed(&mut srs).unwrap();
+        let reader = SrsReader::new(&srs[..], "srs", SrsFormat::KzgPowers, Compress::Yes).unwrap();
+        assert!(matches!(
+            reader.last(),
+            Some(Err(Error::InvalidSrs {
+                source: SerializationError::InvalidData,
+                ..
+            }))
+        ));
+
+        std::fs::remove_file(points_name).unwrap();
+        std::fs::remove_file(srs_name).unwrap();
+    }
 }
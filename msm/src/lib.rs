@@ -1,7 +1,8 @@
 //! Host-side application to use the FPGA-side application.
 //!
 //! Cyclone MSM currently only supports the G1 curve of BLS12-377.
-//! MSM instances of size up to 27 are supported.
+//! MSM instances of length up to [`MAX_LEN`][app::MAX_LEN] = 2^27 are supported;
+//! the length need not be a power of two.
 //!
 //! Steps:
 //! - preprocess points, stream to FPGA
@@ -19,21 +20,133 @@ pub mod bls12_377;
 
 pub mod io;
 
+pub mod multi;
+pub use multi::MultiApp;
+
 pub mod precompute;
 
 pub mod preprocess;
 
+pub mod sim;
+pub use sim::Sim;
+
+pub mod state;
+
 pub mod testing;
 
 pub mod timing;
 
 use ark_bls12_377::{Fr, G1Projective};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("MSM size {0} exceeds the maximum size {}", app::MAX_SIZE)]
+    SizeOutOfRange(u8),
+    #[error("MSM length {0} exceeds the maximum length {}", app::MAX_LEN)]
+    LengthOutOfRange(usize),
+    #[error(
+        "digit width {0} is outside {}..={}",
+        precompute::DigitWidth::MIN,
+        precompute::DigitWidth::MAX
+    )]
+    DigitWidthOutOfRange(u8),
+    #[error("column range {start}..{end} exceeds the {columns} columns")]
+    ColumnRangeOutOfBounds {
+        start: usize,
+        end: usize,
+        columns: usize,
+    },
+    #[error("bucket range {start}..{end} is empty, or exceeds the {buckets} buckets")]
+    BucketRangeOutOfBounds { start: u32, end: u32, buckets: u32 },
+    #[error("{passes} bucket passes do not fit the {buckets} buckets of the range")]
+    BucketPassesOutOfRange { passes: usize, buckets: u32 },
+    #[error("expected {expected} entries, got {actual}")]
+    LengthMismatch { expected: usize, actual: usize },
+    #[error("range {start}..{end} exceeds the {len} loaded points")]
+    RangeOutOfBounds {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    #[error("I/O error on {name}")]
+    Io {
+        name: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("file {name} has {actual}B, expected {expected}B")]
+    CorruptedFile {
+        name: String,
+        expected: u64,
+        actual: u64,
+    },
+    #[error(
+        "FPGA timed out waiting for {waiting_for:?} at stream offset {offset}: {statistics:?}"
+    )]
+    Timeout {
+        waiting_for: app::Wait,
+        offset: usize,
+        statistics: app::Statistics,
+    },
+    #[error("FPGA image {image} is incompatible, load {expected}")]
+    IncompatibleImage {
+        image: String,
+        expected: &'static str,
+    },
+    #[error("point format {0:?} is not supported by the FPGA image")]
+    UnsupportedPointFormat(app::PointFormat),
+    #[error("invalid point file {name}: {reason}")]
+    InvalidPointFile { name: String, reason: String },
+    #[error("invalid serialized points {name}")]
+    InvalidSrs {
+        name: String,
+        #[source]
+        source: ark_serialize::SerializationError,
+    },
+    #[error("state file {0} is owned by another user")]
+    UntrustedState(String),
+    #[error("state file {0} is corrupted")]
+    CorruptedState(String),
+    #[error("preloaded points {found:?} do not match the expected {expected:?}")]
+    PreloadedMismatch {
+        expected: state::Fingerprint,
+        found: Option<state::Fingerprint>,
+    },
+    #[error("no FPGAs given")]
+    NoFpgas,
+    #[error(transparent)]
+    Fpga(#[from] fpga::Error),
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
 
 /// Host-side Cyclone MSM application.
-pub struct App {
-    pub fpga: Fpga,
+///
+/// Generic over the FPGA backend, which defaults to the [`Fpga`] selected by the "hw" feature.
+pub struct App<F = Fpga> {
+    pub fpga: F,
     len: usize,
-    carried: Option<Vec<Scalar>>,
+    width: precompute::DigitWidth,
+    columns: core::ops::Range<usize>,
+    buckets: core::ops::Range<u32>,
+    passes: usize,
+    point_format: app::PointFormat,
+    // image identified on construction
+    image: Option<&'static app::Image>,
+    // slot of the FPGA, whose state file is invalidated whenever the points change
+    slot: Option<i32>,
+    state_file: Option<std::path::PathBuf>,
+    fingerprint: Option<state::Fingerprint>,
+    digits: Option<precompute::Digits>,
+    // second buffer for batched MSMs, allocated on first use
+    next_digits: precompute::Digits,
+    timeouts: app::Timeouts,
+    // read back column sums while the next column streams
+    overlapped_readback: bool,
+    // packets streamed in the last column
+    offset: usize,
+    timeline: Vec<timing::Span<app::Activity>>,
 }
 
 #[repr(u64)]
@@ -46,7 +159,7 @@ pub enum Command {
 /// Packet of 8 commands, streamed to FPGA during MSM column processing.
 pub type Packet = fpga::Aligned<[u64; 8]>;
 
-/// Signed 16-bit digit.
+/// Signed digit, of at most 16 bits, cf. [`DigitWidth`][precompute::DigitWidth].
 pub type Digit = i16;
 /// Unsigned 64-bit limb of a scalar
 pub type Limb = u64;
@@ -54,11 +167,21 @@ pub type Limb = u64;
 pub type Scalar = [Limb; 4];
 
 /// FPGA constructor, independent of "hw" feature.
-#[cfg(feature = "hw")]
 pub fn fpga() -> fpga::Result<Fpga> {
-    Fpga::new(0, 0x500, 0, 0x1_0000_0000)
+    fpga_in_slot(0)
+}
+
+/// FPGA constructor for the given slot, independent of "hw" feature.
+#[cfg(feature = "hw")]
+pub fn fpga_in_slot(slot: i32) -> fpga::Result<Fpga> {
+    Fpga::new(slot, 0x500, 0, 0x1_0000_0000)
 }
 #[cfg(not(feature = "hw"))]
-pub fn fpga() -> fpga::Result<Fpga> {
+pub fn fpga_in_slot(_slot: i32) -> fpga::Result<Fpga> {
     Ok(Fpga::new())
 }
+
+/// FPGA constructors for several slots, e.g. for a [`MultiApp`].
+pub fn fpgas(slots: impl IntoIterator<Item = i32>) -> fpga::Result<Vec<Fpga>> {
+    slots.into_iter().map(fpga_in_slot).collect()
+}
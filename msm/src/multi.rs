@@ -0,0 +1,219 @@
+//! MSMs split across several FPGAs, e.g. all slots of an f1.4xlarge or f1.16xlarge.
+use core::ops::Range;
+
+use ark_bls12_377::{G1Affine, G1TEProjective};
+use ark_std::Zero;
+
+use fpga::{Dma, ReadWrite, Write};
+
+use crate::{
+    app::Images,
+    bls12_377::{into_weierstrass, G1PTEAffine},
+    precompute::{DigitWidth, Digits},
+    timing::timed,
+    App, Error, Fpga, G1Projective, Packet, Result, Scalar,
+};
+
+/// How an MSM is split across FPGAs.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Split {
+    /// Each FPGA holds a consecutive part of the points, and processes the matching scalars.
+    Points,
+    /// Each FPGA holds all points, and processes a consecutive part of the digit columns.
+    Columns,
+}
+
+/// Host-side application driving several FPGAs, each with its own [`App`].
+///
+/// The partial results of the FPGAs are added up on the host.
+pub struct MultiApp<F = Fpga> {
+    apps: Vec<App<F>>,
+    len: usize,
+    split: Split,
+    // digits of the current MSM, shared by all FPGAs when splitting by columns
+    digits: Digits,
+}
+
+/// Part `i` of `n` consecutive parts of `0..len`.
+fn part(len: usize, n: usize, i: usize) -> Range<usize> {
+    len * i / n..len * (i + 1) / n
+}
+
+impl<F: Dma + Images + ReadWrite<u32> + Write<Packet> + Send> MultiApp<F> {
+    /// App for MSMs of length `len`, split across the given FPGAs.
+    pub fn new(fpgas: Vec<F>, len: usize, split: Split) -> Result<Self> {
+        if fpgas.is_empty() {
+            return Err(Error::NoFpgas);
+        }
+        let n = fpgas.len();
+        let apps = fpgas
+            .into_iter()
+            .enumerate()
+            .map(|(i, fpga)| match split {
+                Split::Points => App::with_len(fpga, part(len, n, i).len()),
+                Split::Columns => App::with_len(fpga, len),
+            })
+            .collect::<Result<_>>()?;
+
+        let mut app = MultiApp {
+            apps,
+            len,
+            split,
+            digits: Digits::new(),
+        };
+        app.set_digit_width(DigitWidth::default());
+        Ok(app)
+    }
+
+    pub fn apps(&self) -> &[App<F>] {
+        &self.apps
+    }
+
+    pub fn apps_mut(&mut self) -> &mut [App<F>] {
+        &mut self.apps
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub const fn split(&self) -> Split {
+        self.split
+    }
+
+    /// Set the digit width of all FPGAs, and re-partition the columns if split by columns.
+    pub fn set_digit_width(&mut self, width: DigitWidth) {
+        let n = self.apps.len();
+        for (i, app) in self.apps.iter_mut().enumerate() {
+            app.set_digit_width(width);
+            if self.split == Split::Columns {
+                app.set_column_range(part(width.columns(), n, i))
+                    .unwrap_or_else(|_| unreachable!());
+            }
+        }
+    }
+
+    /// Points held by FPGA `i`.
+    fn points(&self, i: usize) -> Range<usize> {
+        match self.split {
+            Split::Points => part(self.len, self.apps.len(), i),
+            Split::Columns => 0..self.len,
+        }
+    }
+
+    /// Run `f` on all FPGAs in parallel, each with the range of its points.
+    fn each<R: Send>(
+        &mut self,
+        f: impl Fn(&mut App<F>, Range<usize>) -> Result<R> + Sync,
+    ) -> Result<Vec<R>> {
+        let ranges: Vec<_> = (0..self.apps.len()).map(|i| self.points(i)).collect();
+        std::thread::scope(|s| {
+            let handles: Vec<_> = self
+                .apps
+                .iter_mut()
+                .zip(ranges)
+                .map(|(app, range)| s.spawn(|| f(app, range)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        })
+    }
+
+    pub fn set_preprocessed_points(&mut self, points: &[G1PTEAffine]) -> Result<()> {
+        if points.len() != self.len {
+            return Err(Error::LengthMismatch {
+                expected: self.len,
+                actual: points.len(),
+            });
+        }
+        self.each(|app, range| app.set_preprocessed_points(&points[range]))?;
+        Ok(())
+    }
+
+    pub fn set_points(&mut self, points: &[G1Affine]) -> Result<()> {
+        let preprocessed_points: Vec<_> = points.iter().map(|point| point.into()).collect();
+        self.set_preprocessed_points(&preprocessed_points)
+    }
+
+    /// Perform full MSM, split across the FPGAs.
+    pub fn msm(&mut self, scalars: &[Scalar]) -> Result<G1Projective> {
+        if scalars.len() != self.len {
+            return Err(Error::LengthMismatch {
+                expected: self.len,
+                actual: scalars.len(),
+            });
+        }
+
+        let partials = match self.split {
+            Split::Points => {
+                self.each(|app, range| app.msm_te(0..range.len(), scalars[range].iter()))?
+            }
+            Split::Columns => {
+                // the digits are calculated once, for all FPGAs
+                let mut digits = core::mem::take(&mut self.digits);
+                let width = self.apps[0].digit_width();
+                timed("digits", || digits.compute(width, 0, scalars.iter()));
+                let partials = self.each(|app, _| app.msm_digits(&digits));
+                self.digits = digits;
+                partials?
+            }
+        };
+
+        let total = partials
+            .into_iter()
+            .fold(G1TEProjective::zero(), |total, partial| total + partial);
+        Ok(into_weierstrass(&total))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        testing::{harness_points, harness_scalars},
+        Sim,
+    };
+
+    #[test]
+    fn multi_app() {
+        let size = 6;
+        let (beta, points) = harness_points(size);
+        let (scalars, sum) = harness_scalars(&beta, size);
+
+        for split in [Split::Points, Split::Columns] {
+            for n in [1, 3, 4] {
+                let fpgas = (0..n).map(|_| Sim::new()).collect();
+                let mut app = MultiApp::new(fpgas, 1 << size, split).unwrap();
+                app.set_preprocessed_points(&points).unwrap();
+                assert_eq!(app.msm(&scalars).unwrap(), sum);
+
+                let held = app.apps().iter().map(|app| app.fpga.points().len());
+                match split {
+                    Split::Points => assert_eq!(held.sum::<usize>(), 1 << size),
+                    Split::Columns => assert!(held.into_iter().all(|len| len == 1 << size)),
+                }
+            }
+        }
+
+        // more FPGAs than columns
+        let fpgas = (0..20).map(|_| Sim::new()).collect();
+        let mut app = MultiApp::new(fpgas, 1 << size, Split::Columns).unwrap();
+        app.set_preprocessed_points(&points).unwrap();
+        assert_eq!(app.msm(&scalars).unwrap(), sum);
+
+        let width = DigitWidth::new(13).unwrap();
+        app.set_digit_width(width);
+        assert_eq!(app.apps()[19].column_range().end, width.columns());
+        assert_eq!(app.msm(&scalars).unwrap(), sum);
+    }
+}
@@ -1,6 +1,6 @@
 //! Scalar precomputation.
 
-use crate::Scalar;
+use crate::{Error, Result, Scalar};
 
 #[inline(always)]
 pub fn single_digit_carry(carried: &Scalar, i: usize, j: u8) -> i16 {
@@ -20,7 +20,7 @@ pub fn single_digit_carry(carried: &Scalar, i: usize, j: u8) -> i16 {
 
 #[inline]
 pub fn limb_carries<'a>(
-    scalars: impl Iterator<Item = &'a Scalar> + ExactSizeIterator + Send,
+    scalars: impl ExactSizeIterator<Item = &'a Scalar> + Send,
     carried_limbs: &mut [Scalar],
 ) {
     const HI: u16 = 1 << 15;
@@ -155,6 +155,398 @@ pub fn unrolled_signed_digit_16(scalar: &Scalar, i: usize) -> i32 {
     unreachable!();
 }
 
+/// All 16 signed digits of a scalar, in one pass of digit-level carries.
+#[inline(always)]
+pub fn signed_digits_16(scalar: &Scalar) -> [i16; 16] {
+    const MAX_SIGNED: u32 = 1 << 15;
+    let mut carry = 0u32;
+    let mut digits = [0i16; 16];
+    seq_macro::seq!(j in 0..16 {
+        let unsigned_digit = unsigned_digit_16(scalar, j) + carry;
+        carry = (unsigned_digit + MAX_SIGNED) >> 16;
+        digits[j] = ((unsigned_digit as i32) - ((carry as i32) << 16)) as i16;
+    });
+    digits
+}
+
+/// Bits of a scalar, as decomposed into digits.
+pub const SCALAR_BITS: usize = 256;
+
+/// Bits per signed digit, i.e. the window size of the bucket method.
+///
+/// Digits of width `w` lie in `[-2^(w-1), 2^(w-1))`, so the FPGA app needs `2^(w-1)` buckets.
+/// The `SetDigit` command carries 16-bit digits, which limits the width to 16 bits.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DigitWidth(u8);
+
+impl DigitWidth {
+    pub const MIN: u8 = 2;
+    pub const MAX: u8 = 16;
+
+    pub fn new(bits: u8) -> Result<Self> {
+        if !(Self::MIN..=Self::MAX).contains(&bits) {
+            return Err(Error::DigitWidthOutOfRange(bits));
+        }
+        Ok(Self(bits))
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Number of digit columns of a scalar.
+    pub const fn columns(self) -> usize {
+        (SCALAR_BITS + self.0 as usize - 1) / self.0 as usize
+    }
+
+    /// Number of buckets, indexed by the absolute value of the digit minus one.
+    pub const fn buckets(self) -> u32 {
+        1 << (self.0 - 1)
+    }
+}
+
+impl Default for DigitWidth {
+    fn default() -> Self {
+        Self(16)
+    }
+}
+
+/// Signed digits of the given width of a scalar, least significant first.
+///
+/// The carry out of the last digit is dropped, so `digits` should cover [`SCALAR_BITS`].
+#[inline]
+pub fn signed_digits(scalar: &Scalar, width: DigitWidth, digits: &mut [i16]) {
+    let bits = width.bits() as usize;
+    let max_signed = 1u32 << (bits - 1);
+    let mut carry = 0u32;
+    for (k, digit) in digits.iter_mut().enumerate() {
+        let unsigned_digit = unsigned_digit(scalar, k * bits, bits) + carry;
+        carry = (unsigned_digit + max_signed) >> bits;
+        *digit = ((unsigned_digit as i32) - ((carry as i32) << bits)) as i16;
+    }
+}
+
+/// The `bits` bits of a scalar starting at bit `offset`, which may straddle two limbs.
+#[inline(always)]
+fn unsigned_digit(scalar: &Scalar, offset: usize, bits: usize) -> u32 {
+    let (limb, shift) = (offset / 64, offset % 64);
+    let mut digit = scalar.get(limb).map_or(0, |limb| limb >> shift);
+    if shift + bits > 64 {
+        digit |= scalar.get(limb + 1).map_or(0, |limb| limb << (64 - shift));
+    }
+    (digit & ((1 << bits) - 1)) as u32
+}
+
+/// Implementation of the digit decomposition of blocks of scalars, for 16-bit digits.
+///
+/// The SIMD kernels decompose 8 (AVX2) or 16 (AVX-512) scalars at once, using
+/// 32-bit lanes for the digit-level carries.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Kernel {
+    /// one scalar at a time, via [`signed_digits_16`]
+    Portable,
+    Avx2,
+    /// needs the "avx512" feature, and Rust 1.89 to build, cf. `build.rs`
+    Avx512,
+}
+
+impl Kernel {
+    /// Fastest kernel supported by the CPU.
+    pub fn detect() -> Self {
+        [Kernel::Avx512, Kernel::Avx2]
+            .into_iter()
+            .find(|kernel| kernel.is_supported())
+            .unwrap_or(Kernel::Portable)
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Kernel::Portable => true,
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(all(target_arch = "x86_64", cyclone_avx512))]
+            Kernel::Avx512 => is_x86_feature_detected!("avx512f"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Digits of a block of scalars: `chunks[h][k]` holds digit `k` of scalars `8h..8h + 8`.
+    ///
+    /// Panics if the kernel is not supported.
+    #[inline]
+    pub fn signed_digits(
+        self,
+        scalars: &[Scalar; 16],
+        chunks: &mut [[DigitChunk; NUM_COLUMNS]; 2],
+    ) {
+        assert!(self.is_supported());
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Kernel::Avx2 => {
+                for (scalars, chunks) in scalars.chunks_exact(8).zip(chunks.iter_mut()) {
+                    let scalars = scalars.try_into().unwrap_or_else(|_| unreachable!());
+                    // SAFETY: AVX2 support is checked above
+                    *chunks = unsafe { x86::signed_digits_avx2(scalars) };
+                }
+            }
+            #[cfg(all(target_arch = "x86_64", cyclone_avx512))]
+            Kernel::Avx512 => {
+                // SAFETY: AVX-512 support is checked above
+                *chunks = unsafe { x86::signed_digits_avx512(scalars) };
+            }
+            _ => {
+                for (l, scalar) in scalars.iter().enumerate() {
+                    let (h, l) = (l / DIGITS_PER_PACKET, l % DIGITS_PER_PACKET);
+                    for (chunk, digit) in chunks[h].iter_mut().zip(signed_digits_16(scalar)) {
+                        chunk.0[l] = digit;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use core::arch::x86_64::*;
+
+    use super::{DigitChunk, NUM_COLUMNS};
+    use crate::Scalar;
+
+    // the 32-bit words of a scalar each contain two digits
+    const WORDS: i32 = 8;
+
+    /// Signed digits of 8 scalars, one per 32-bit lane.
+    ///
+    /// With `u` the unsigned digit plus incoming carry, the outgoing carry is
+    /// `(u + 2^15) >> 16`, and the signed digit is `u` truncated to 16 bits.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn signed_digits_avx2(scalars: &[Scalar; 8]) -> [DigitChunk; NUM_COLUMNS] {
+        let base = scalars.as_ptr() as *const i32;
+        let offsets = _mm256_setr_epi32(
+            0,
+            WORDS,
+            2 * WORDS,
+            3 * WORDS,
+            4 * WORDS,
+            5 * WORDS,
+            6 * WORDS,
+            7 * WORDS,
+        );
+        let low = _mm256_set1_epi32(0xFFFF);
+        let half = _mm256_set1_epi32(1 << 15);
+
+        let mut carry = _mm256_setzero_si256();
+        let mut digits = [_mm256_setzero_si256(); NUM_COLUMNS];
+        for m in 0..WORDS as usize {
+            let words = _mm256_i32gather_epi32::<4>(base.add(m), offsets);
+            let unsigned = [_mm256_and_si256(words, low), _mm256_srli_epi32::<16>(words)];
+            for (digit, unsigned) in digits[2 * m..].iter_mut().zip(unsigned) {
+                let u = _mm256_add_epi32(unsigned, carry);
+                carry = _mm256_srli_epi32::<16>(_mm256_add_epi32(u, half));
+                *digit = _mm256_and_si256(u, low);
+            }
+        }
+
+        let mut chunks = [DigitChunk::default(); NUM_COLUMNS];
+        for k in (0..NUM_COLUMNS).step_by(2) {
+            // packing interleaves the 128-bit halves of both inputs
+            let packed = _mm256_packus_epi32(digits[k], digits[k + 1]);
+            let packed = _mm256_permute4x64_epi64::<0b11_01_10_00>(packed);
+            _mm256_storeu_si256(chunks[k..].as_mut_ptr() as *mut __m256i, packed);
+        }
+        chunks
+    }
+
+    /// Signed digits of 16 scalars, one per 32-bit lane, cf. [`signed_digits_avx2`].
+    #[cfg(cyclone_avx512)]
+    #[clippy::msrv = "1.89"]
+    #[target_feature(enable = "avx512f")]
+    pub unsafe fn signed_digits_avx512(scalars: &[Scalar; 16]) -> [[DigitChunk; NUM_COLUMNS]; 2] {
+        let base = scalars.as_ptr() as *const i32;
+        let offsets = _mm512_mullo_epi32(
+            _mm512_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15),
+            _mm512_set1_epi32(WORDS),
+        );
+        let low = _mm512_set1_epi32(0xFFFF);
+        let half = _mm512_set1_epi32(1 << 15);
+
+        let mut carry = _mm512_setzero_si512();
+        let mut chunks = [[DigitChunk::default(); NUM_COLUMNS]; 2];
+        for m in 0..WORDS as usize {
+            let words = _mm512_i32gather_epi32::<4>(offsets, base.add(m) as _);
+            let unsigned = [_mm512_and_si512(words, low), _mm512_srli_epi32::<16>(words)];
+            for (k, unsigned) in (2 * m..).zip(unsigned) {
+                let u = _mm512_add_epi32(unsigned, carry);
+                carry = _mm512_srli_epi32::<16>(_mm512_add_epi32(u, half));
+                // truncating to 16 bits yields the signed digits
+                let mut digits = [DigitChunk::default(); 2];
+                _mm256_storeu_si256(
+                    digits.as_mut_ptr() as *mut __m256i,
+                    _mm512_cvtepi32_epi16(u),
+                );
+                chunks[0][k] = digits[0];
+                chunks[1][k] = digits[1];
+            }
+        }
+        chunks
+    }
+}
+
+/// Number of 16-bit digit columns of a scalar.
+pub const NUM_COLUMNS: usize = 16;
+/// Number of digit columns of a scalar of the smallest digit width.
+pub const MAX_COLUMNS: usize = SCALAR_BITS / DigitWidth::MIN as usize;
+/// Digits per packet of `SetDigit` commands.
+pub const DIGITS_PER_PACKET: usize = 8;
+// spawning threads for fewer scalars is not worth it
+const MIN_SCALARS_PER_THREAD: usize = 1 << 12;
+
+/// The digits of one packet of `SetDigit` commands.
+#[repr(align(16))]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct DigitChunk(pub [i16; DIGITS_PER_PACKET]);
+
+/// Signed digits of all columns of an MSM, precomputed for streaming.
+///
+/// Column `k` contains digit `k` of [`signed_digits`] of all scalars, in chunks of one
+/// packet each. Buffers are reused across MSMs.
+#[derive(Clone, Debug, Default)]
+pub struct Digits {
+    len: usize,
+    width: DigitWidth,
+    columns: Vec<Vec<DigitChunk>>,
+}
+
+impl Digits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of digits per column.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn width(&self) -> DigitWidth {
+        self.width
+    }
+
+    /// Number of digit columns.
+    pub fn columns(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Digits of column `k`; the digits of the last chunk past [`Digits::len`] are zero.
+    pub fn column(&self, k: usize) -> &[DigitChunk] {
+        &self.columns[k]
+    }
+
+    /// Calculate the digits of `skip` zero scalars followed by `scalars`, in parallel.
+    pub fn compute<'a>(
+        &mut self,
+        width: DigitWidth,
+        skip: usize,
+        scalars: impl ExactSizeIterator<Item = &'a Scalar> + Clone + Send,
+    ) {
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let threads = threads.min((skip + scalars.len()) / MIN_SCALARS_PER_THREAD);
+        self.compute_with_threads(width, skip, scalars, threads);
+    }
+
+    /// Like [`Digits::compute`], with the given number of threads.
+    pub fn compute_with_threads<'a>(
+        &mut self,
+        width: DigitWidth,
+        skip: usize,
+        scalars: impl ExactSizeIterator<Item = &'a Scalar> + Clone + Send,
+        threads: usize,
+    ) {
+        self.len = skip + scalars.len();
+        self.width = width;
+        let chunks = (self.len + DIGITS_PER_PACKET - 1) / DIGITS_PER_PACKET;
+        self.columns.resize_with(width.columns(), Vec::new);
+        for column in self.columns.iter_mut() {
+            column.clear();
+            column.resize(chunks, DigitChunk::default());
+        }
+        if chunks == 0 {
+            return;
+        }
+
+        // threads work on disjoint ranges of chunks, in all columns
+        // the SIMD kernels are specific to 16-bit digits
+        let kernel = match width == DigitWidth::default() {
+            true => Kernel::detect(),
+            false => Kernel::Portable,
+        };
+        let threads = threads.clamp(1, chunks);
+        let chunks_per_thread = (chunks + threads - 1) / threads;
+        let scalars_per_thread = chunks_per_thread * DIGITS_PER_PACKET;
+        let mut columns: Vec<_> = self
+            .columns
+            .iter_mut()
+            .map(|column| column.chunks_mut(chunks_per_thread))
+            .collect();
+
+        std::thread::scope(|s| {
+            let mut start = 0;
+            while start < self.len {
+                let mut parts: Vec<&mut [DigitChunk]> = columns
+                    .iter_mut()
+                    .map(|column| column.next().unwrap_or_else(|| unreachable!()))
+                    .collect();
+                let end = self.len.min(start + scalars_per_thread);
+                let zeros = skip.clamp(start, end) - start;
+                let scalars = scalars.clone().skip(start.saturating_sub(skip));
+                let scalars = scalars.take(end - start - zeros);
+                s.spawn(move || {
+                    let mut scalars = scalars;
+                    let mut block = [Scalar::default(); 2 * DIGITS_PER_PACKET];
+                    let mut digits = [[DigitChunk::default(); NUM_COLUMNS]; 2];
+                    for chunk in (0..parts[0].len()).step_by(2) {
+                        // the skipped scalars, and the padding, are zero
+                        for (index, scalar) in (chunk * DIGITS_PER_PACKET..).zip(block.iter_mut()) {
+                            *scalar = match index < zeros {
+                                true => Scalar::default(),
+                                false => scalars.next().copied().unwrap_or_default(),
+                            };
+                        }
+                        if kernel != Kernel::Portable {
+                            kernel.signed_digits(&block, &mut digits);
+                            for (part, k) in parts.iter_mut().zip(0..) {
+                                part[chunk] = digits[0][k];
+                                if let Some(next) = part.get_mut(chunk + 1) {
+                                    *next = digits[1][k];
+                                }
+                            }
+                            continue;
+                        }
+
+                        for (l, scalar) in block.iter().enumerate() {
+                            let chunk = chunk + l / DIGITS_PER_PACKET;
+                            if chunk == parts[0].len() {
+                                break;
+                            }
+                            let mut digits = [0; MAX_COLUMNS];
+                            signed_digits(scalar, width, &mut digits[..parts.len()]);
+                            for (part, digit) in parts.iter_mut().zip(digits) {
+                                part[chunk].0[l % DIGITS_PER_PACKET] = digit;
+                            }
+                        }
+                    }
+                });
+                start = end;
+            }
+        });
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -173,7 +565,7 @@ mod test {
 
         // perform the initial limb-level carries
         let mut carried = vec![Scalar::default(); scalars.len()];
-        limb_carries(&scalars, carried.as_mut_slice());
+        limb_carries(scalars.iter(), carried.as_mut_slice());
 
         // the actual test: all digit-level carries
         for (point, (scalar, carried)) in scalars.iter().zip(carried.iter()).enumerate() {
@@ -199,4 +591,114 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn kernels() {
+        let size = 16;
+        let mut scalars = crate::testing::random_scalars(size);
+
+        // the edge cases of `fast_digits`
+        scalars[0] = [u64::MAX, scalars[0][1], scalars[0][2], scalars[0][3]];
+        scalars[1] = [scalars[1][0], u64::MAX, scalars[1][2], scalars[1][3]];
+        scalars[2] = [scalars[2][0], scalars[2][1], u64::MAX, scalars[2][3]];
+        scalars[3] = [u64::MAX, u64::MAX, scalars[3][2], scalars[3][3]];
+        scalars[4] = [u64::MAX, u64::MAX, u64::MAX, scalars[4][3]];
+        // and some more carry chains, in the second half of a block
+        scalars[21] = [u64::MAX, u64::MAX, u64::MAX, scalars[21][3] >> 1];
+        scalars[22] = [1 << 15; 4];
+        scalars[23] = [0x7FFF_8000_7FFF_8000; 4];
+        scalars[24] = Scalar::default();
+
+        let kernels = [Kernel::Portable, Kernel::Avx2, Kernel::Avx512];
+        for kernel in kernels.into_iter().filter(|kernel| kernel.is_supported()) {
+            println!("kernel = {kernel:?}");
+            let mut digits = [[DigitChunk::default(); NUM_COLUMNS]; 2];
+            for block in scalars.chunks_exact(16) {
+                kernel.signed_digits(block.try_into().unwrap(), &mut digits);
+                for (l, scalar) in block.iter().enumerate() {
+                    for (k, chunk) in digits[l / 8].iter().enumerate() {
+                        assert_eq!(chunk.0[l % 8], unrolled_signed_digit_16(scalar, k) as i16);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn digits() {
+        let scalars = crate::testing::random_scalars(13);
+        let scalars = &scalars[..(1 << 13) - 5];
+
+        let mut digits = Digits::new();
+        let width = DigitWidth::default();
+        for (skip, threads) in [(0, 1), (0, 4), (3, 3), (9000, 4), (9000, 1)] {
+            digits.compute_with_threads(width, skip, scalars.iter(), threads);
+            assert_eq!(digits.len(), skip + scalars.len());
+
+            for k in 0..NUM_COLUMNS {
+                let column = digits.column(k);
+                assert_eq!(column.len(), (digits.len() + 7) / 8);
+                let expected = core::iter::repeat(0)
+                    .take(skip)
+                    .chain(
+                        scalars
+                            .iter()
+                            .map(|scalar| unrolled_signed_digit_16(scalar, k) as i16),
+                    )
+                    .chain(core::iter::repeat(0));
+                for (digit, expected) in column.iter().flat_map(|chunk| chunk.0).zip(expected) {
+                    assert_eq!(digit, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn digit_widths() {
+        use ark_ff::{BigInteger as _, PrimeField as _};
+
+        let scalars: Vec<_> = crate::testing::random_fr(8)
+            .iter()
+            .map(|scalar| scalar.into_bigint().0)
+            .collect();
+
+        let mut digits = [0; MAX_COLUMNS];
+        for scalar in &scalars {
+            signed_digits(scalar, DigitWidth::default(), &mut digits[..NUM_COLUMNS]);
+            for (k, digit) in digits[..NUM_COLUMNS].iter().enumerate() {
+                assert_eq!(*digit, unrolled_signed_digit_16(scalar, k) as i16);
+            }
+        }
+
+        for bits in [2, 7, 12, 13, 15] {
+            let width = DigitWidth::new(bits).unwrap();
+            let mut parallel = Digits::new();
+            parallel.compute_with_threads(width, 5, scalars.iter(), 3);
+            assert_eq!(parallel.columns(), width.columns());
+
+            for (index, scalar) in scalars.iter().enumerate() {
+                let digits = &mut digits[..width.columns()];
+                signed_digits(scalar, width, digits);
+
+                // recompose the scalar from its digits
+                let mut recomposed = ark_ff::BigInt::<4>::zero();
+                for (k, &digit) in digits.iter().enumerate().rev() {
+                    assert!((digit as i32).unsigned_abs() <= width.buckets());
+                    recomposed.muln(bits as u32);
+                    let magnitude = ark_ff::BigInt::from((digit as i32).unsigned_abs() as u64);
+                    match digit < 0 {
+                        true => recomposed.sub_with_borrow(&magnitude),
+                        false => recomposed.add_with_carry(&magnitude),
+                    };
+
+                    let (chunk, l) = ((5 + index) / 8, (5 + index) % 8);
+                    assert_eq!(parallel.column(k)[chunk].0[l], digit);
+                }
+                assert_eq!(&recomposed.0, scalar);
+            }
+        }
+
+        assert!(DigitWidth::new(1).is_err());
+        assert!(DigitWidth::new(17).is_err());
+    }
 }
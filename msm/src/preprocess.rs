@@ -1,6 +1,7 @@
 //! Point preprocessing.
 
 use core::ops::{AddAssign, Neg, SubAssign};
+use std::collections::VecDeque;
 
 pub use ark_bls12_377::g1::Parameters;
 use ark_bls12_377::{Fq, G1Affine};
@@ -22,6 +23,8 @@ use crate::bls12_377::G1PTEAffine;
 #[derivative(Copy(bound = "P: TECurveConfig"), Clone(bound = "P: TECurveConfig"))]
 // #[must_use]
 #[derive(Debug, Eq, Hash, PartialEq)]
+// fixed layout, as point files are mapped into memory
+#[repr(C)]
 pub struct PreprocessedAffine<P: TECurveConfig> {
     /// X coordinate of the point represented as a field element
     pub x: P::BaseField,
@@ -228,15 +231,126 @@ pub fn batch_preprocess(a: &[G1Affine], b: &mut [G1PTEAffine]) {
 //     }
 // }
 
+/// Points per batch inversion.
+pub const CHUNK: usize = 1 << 16;
+
+fn threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+/// Preprocess the points in chunks of [`CHUNK`], spread over all cores.
+///
+/// The result does not depend on the number of cores.
 pub fn preprocess_points(points: &[G1Affine]) -> Vec<G1PTEAffine> {
     let mut ppoints = vec![G1PTEAffine::zero(); points.len()];
 
-    const CHUNK: usize = 1 << 16;
-    for (chunk_in, chunk_out) in points
-        .chunks(CHUNK)
-        .zip(ppoints.as_mut_slice().chunks_mut(CHUNK))
-    {
-        crate::preprocess::batch_preprocess(chunk_in, chunk_out);
-    }
+    let chunks = (points.len() + CHUNK - 1) / CHUNK;
+    let per_thread = ((chunks + threads() - 1) / threads()).max(1) * CHUNK;
+    std::thread::scope(|s| {
+        for (points, ppoints) in points
+            .chunks(per_thread)
+            .zip(ppoints.chunks_mut(per_thread))
+        {
+            s.spawn(move || {
+                for (chunk_in, chunk_out) in points.chunks(CHUNK).zip(ppoints.chunks_mut(CHUNK)) {
+                    batch_preprocess(chunk_in, chunk_out);
+                }
+            });
+        }
+    });
     ppoints
 }
+
+/// Preprocess points in chunks of [`CHUNK`], holding one chunk per core in memory.
+///
+/// Yields the preprocessed chunks, or the first error of `points`.
+pub fn preprocess_chunks<I, E>(points: I) -> PreprocessChunks<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<G1Affine, E>>,
+{
+    PreprocessChunks {
+        points: points.into_iter(),
+        batch: Vec::with_capacity(threads() * CHUNK),
+        threads: threads(),
+        preprocessed: VecDeque::new(),
+    }
+}
+
+/// Iterator of [`preprocess_chunks`].
+#[derive(Clone)]
+pub struct PreprocessChunks<I> {
+    points: I,
+    // reused buffer of affine points, a chunk per thread
+    batch: Vec<G1Affine>,
+    threads: usize,
+    // preprocessed chunks of the batch, not yet yielded
+    preprocessed: VecDeque<Vec<G1PTEAffine>>,
+}
+
+impl<I: Iterator<Item = Result<G1Affine, E>>, E> Iterator for PreprocessChunks<I> {
+    type Item = Result<Vec<G1PTEAffine>, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(chunk) = self.preprocessed.pop_front() {
+            return Some(Ok(chunk));
+        }
+        self.batch.clear();
+        for point in self.points.by_ref() {
+            match point {
+                Ok(point) => self.batch.push(point),
+                Err(err) => return Some(Err(err)),
+            }
+            if self.batch.len() == self.threads * CHUNK {
+                break;
+            }
+        }
+        if self.batch.is_empty() {
+            return None;
+        }
+        self.preprocessed = self
+            .batch
+            .chunks(CHUNK)
+            .map(|chunk| vec![G1PTEAffine::zero(); chunk.len()])
+            .collect();
+        let batch = &self.batch;
+        let preprocessed = &mut self.preprocessed;
+        std::thread::scope(|s| {
+            for (chunk_in, chunk_out) in batch.chunks(CHUNK).zip(preprocessed.iter_mut()) {
+                s.spawn(move || batch_preprocess(chunk_in, chunk_out));
+            }
+        });
+        self.preprocessed.pop_front().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chunks() {
+        let points = crate::testing::random_points(4);
+        let points: Vec<_> = points.iter().copied().cycle().take(2 * CHUNK + 3).collect();
+        let mut serial = vec![G1PTEAffine::zero(); points.len()];
+        for (chunk_in, chunk_out) in points.chunks(CHUNK).zip(serial.chunks_mut(CHUNK)) {
+            batch_preprocess(chunk_in, chunk_out);
+        }
+        assert!(preprocess_points(&points) == serial);
+
+        let chunks: Vec<_> = preprocess_chunks(points.iter().map(|&point| Ok::<_, ()>(point)))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+            [CHUNK, CHUNK, 3]
+        );
+        assert!(chunks.concat() == serial);
+
+        let failing = points
+            .iter()
+            .map(|&point| Ok(point))
+            .take(5)
+            .chain([Err(())]);
+        assert!(matches!(preprocess_chunks(failing).next(), Some(Err(()))));
+    }
+}
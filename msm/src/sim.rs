@@ -0,0 +1,445 @@
+//! Software simulation of the FPGA-side application.
+
+use ark_bls12_377::{Fq, Fr, G1TEProjective};
+use ark_ff::PrimeField as _;
+use ark_std::Zero as _;
+
+use fpga::{Dma, Flush, Identify, ReadWrite, Write};
+
+use crate::{
+    app::{
+        DdrLayout, Image, Images, PointFormat, ReadRegister, Stream, WriteRegister, IMAGE, IMAGES,
+    },
+    bls12_377::G1PTEAffine,
+    Command, Packet,
+};
+
+const NUM_BUCKETS: usize = 1 << 15;
+const NUM_STATISTICS: usize = 7;
+
+/// The top-level stream is selected by the bits above this offset.
+const STREAM_SHIFT: u32 = 26;
+const STREAM_OFFSET_MASK: usize = (1 << STREAM_SHIFT) - 1;
+
+/// Opcodes of [`Command`] live in the bits below the digit.
+const COMMAND_MASK: u64 = (1 << 14) - 1;
+
+/// DDR layout of the [`SIM_IMAGE`].
+pub const DDR_LAYOUT: DdrLayout = DdrLayout {
+    base: 0,
+    coordinate_bytes: 64,
+};
+
+/// Simulated image with the features of images under development, i.e. loading points
+/// via DMA and decoding packed points. Select it with [`Sim::set_image_id`].
+///
+/// Only supported on the simulator, cf. [`Images`].
+pub const SIM_IMAGE: Image = Image {
+    id: "cyclone-msm-sim",
+    point_formats: &[PointFormat::Padded, PointFormat::Packed],
+    dma: Some(DDR_LAYOUT),
+};
+
+/// Bit-accurate software model of the Cyclone MSM FPGA app.
+///
+/// Decodes the same register and stream address space as the FPGA image, and
+/// accumulates digits into buckets of extended twisted Edwards points, so that the
+/// complete [`App`][crate::App] code path can be run without an F1 instance.
+///
+/// Like the FPGA, it expects points in Montgomery form and returns aggregated
+/// coordinates in canonical form. All work happens synchronously on writes,
+/// hence the digits queue is always empty. Point indices are limited to `2^26`.
+///
+/// The coordinate registers keep the last column sum until the next aggregation,
+/// which [`App`][crate::App] relies on to read it back during the following column.
+#[derive(Clone)]
+pub struct Sim {
+    points: Vec<G1PTEAffine>,
+    zero: G1TEProjective,
+
+    query: u32,
+    msm_length: u32,
+    first_bucket: u32,
+    last_bucket: u32,
+    ddr_read_len: u32,
+
+    buckets: Vec<G1TEProjective>,
+    touched: Vec<bool>,
+    started: bool,
+    digits: u32,
+    aggregated: bool,
+    sum: [[u64; 6]; 4],
+    statistics: [u32; NUM_STATISTICS],
+    image_id: Option<String>,
+    dma: bool,
+    dma_bytes: usize,
+}
+
+impl Sim {
+    pub fn new() -> Self {
+        Self {
+            points: Vec::new(),
+            zero: G1TEProjective::zero(),
+
+            query: 0,
+            msm_length: 0,
+            first_bucket: 0,
+            last_bucket: NUM_BUCKETS as u32 - 1,
+            ddr_read_len: 0,
+
+            buckets: vec![G1TEProjective::zero(); NUM_BUCKETS],
+            touched: vec![false; NUM_BUCKETS],
+            started: false,
+            digits: 0,
+            aggregated: false,
+            sum: Default::default(),
+            statistics: [0; NUM_STATISTICS],
+            image_id: Some(IMAGE.to_string()),
+            dma: false,
+            dma_bytes: 0,
+        }
+    }
+
+    /// Expose a DMA engine, by default there is none.
+    ///
+    /// Only used by [`App`][crate::App] if the simulated image is the [`SIM_IMAGE`].
+    pub fn set_dma(&mut self, dma: bool) {
+        self.dma = dma;
+    }
+
+    /// Simulate another loaded image, by default [`IMAGE`].
+    pub fn set_image_id(&mut self, image_id: Option<String>) {
+        self.image_id = image_id;
+    }
+
+    /// Buckets selected by the first and last bucket registers.
+    pub fn bucket_range(&self) -> core::ops::RangeInclusive<u32> {
+        self.first_bucket..=self.last_bucket
+    }
+
+    /// Bytes written via DMA so far.
+    pub fn dma_bytes(&self) -> usize {
+        self.dma_bytes
+    }
+
+    /// Points currently stored in the simulated DDR.
+    pub fn points(&self) -> &[G1PTEAffine] {
+        &self.points
+    }
+
+    /// Value of the `DdrReadLen` register, which has no effect on the simulation.
+    pub fn ddr_read_len(&self) -> u32 {
+        self.ddr_read_len
+    }
+
+    /// Decode packet `offset` of packed points: limb `j` of the stream is limb `j % 6` of
+    /// coordinate `j / 6 % 3` (X, Y, kT) of point `j / 18`. Padding of the last group
+    /// lands beyond the last point.
+    fn set_packed_limbs(&mut self, offset: usize, packet: &Packet) {
+        const LIMBS: usize = 6;
+        for (j, &limb) in (offset * packet.len()..).zip(packet.iter()) {
+            let index = j / (3 * LIMBS);
+            let point = self.point_mut(index);
+            let coordinate = match j / LIMBS % 3 {
+                0 => &mut point.x,
+                1 => &mut point.y,
+                _ => &mut point.kt,
+            };
+            coordinate.0 .0[j % LIMBS] = limb;
+        }
+    }
+
+    fn point_mut(&mut self, index: usize) -> &mut G1PTEAffine {
+        if index >= self.points.len() {
+            self.points.resize(index + 1, G1PTEAffine::zero());
+        }
+        &mut self.points[index]
+    }
+
+    fn start_column(&mut self) {
+        for (bucket, touched) in self.buckets.iter_mut().zip(self.touched.iter_mut()) {
+            if *touched {
+                *bucket = self.zero;
+                *touched = false;
+            }
+        }
+        self.started = true;
+        self.digits = 0;
+        self.aggregated = false;
+
+        if self.msm_length == 0 {
+            self.aggregate();
+        }
+    }
+
+    fn set_digit(&mut self, digit: i16) {
+        if !self.started || self.aggregated {
+            self.statistics[crate::app::Statistic::DroppedCommands as usize] += 1;
+            return;
+        }
+
+        let index = self.digits as usize;
+        self.digits += 1;
+
+        // buckets are indexed by the absolute value of the digit, minus one
+        let magnitude = (digit as i32).unsigned_abs();
+        if magnitude != 0 {
+            let bucket = magnitude - 1;
+            if (self.first_bucket..=self.last_bucket).contains(&bucket) {
+                let point = self
+                    .points
+                    .get(index)
+                    .copied()
+                    .unwrap_or_else(G1PTEAffine::zero);
+                let bucket = bucket as usize;
+                if !self.touched[bucket] {
+                    self.buckets[bucket] = self.zero;
+                    self.touched[bucket] = true;
+                }
+                if digit > 0 {
+                    self.buckets[bucket] += &point;
+                } else {
+                    self.buckets[bucket] -= &point;
+                }
+            }
+        }
+
+        if self.digits == self.msm_length {
+            self.aggregate();
+        }
+    }
+
+    /// Calculate `\Sum_b (b + 1) * bucket_b` via running sums over the touched buckets.
+    fn aggregate(&mut self) {
+        let mut running = G1TEProjective::zero();
+        let mut total = G1TEProjective::zero();
+
+        let mut touched = self
+            .touched
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, &touched)| touched)
+            .map(|(bucket, _)| bucket)
+            .peekable();
+
+        while let Some(bucket) = touched.next() {
+            running += self.buckets[bucket];
+            // the running sum is added once for each weight between this and the next bucket
+            let gap = match touched.peek() {
+                Some(&next) => bucket - next,
+                None => bucket + 1,
+            };
+            if gap == 1 {
+                total += running;
+            } else {
+                total += running * Fr::from(gap as u64);
+            }
+        }
+
+        self.aggregated = true;
+        self.sum = [
+            total.x.into_bigint().0,
+            total.y.into_bigint().0,
+            total.z.into_bigint().0,
+            total.t.into_bigint().0,
+        ];
+    }
+
+    fn coordinate_word(&self, coordinate: usize) -> u32 {
+        let query = self.query as usize;
+        match query < 12 {
+            true => (self.sum[coordinate][query / 2] >> (32 * (query % 2))) as u32,
+            false => 0,
+        }
+    }
+}
+
+impl Default for Sim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Coordinates are streamed in Montgomery form, cf. `App::set_coordinates`.
+fn coordinate(packet: &Packet) -> Fq {
+    let mut coordinate = Fq::zero();
+    coordinate.0 .0.copy_from_slice(&packet[..6]);
+    coordinate
+}
+
+impl Images for Sim {
+    /// The [`IMAGES`], and the [`SIM_IMAGE`].
+    fn images(&self) -> &'static [Image] {
+        const SIM_IMAGES: &[Image] = &[IMAGES[0], SIM_IMAGE];
+        SIM_IMAGES
+    }
+}
+
+impl Identify for Sim {
+    fn image_id(&self) -> fpga::Result<Option<String>> {
+        Ok(self.image_id.clone())
+    }
+}
+
+impl Dma for Sim {
+    fn has_dma(&self) -> bool {
+        self.dma
+    }
+
+    /// Decodes whole points in the [`DDR_LAYOUT`], panics on partial points.
+    fn dma_write(&mut self, address: u64, data: &[u8]) -> fpga::Result<()> {
+        if !self.dma {
+            return Err(fpga::Error::NoDma);
+        }
+        let point_bytes = DDR_LAYOUT.point_bytes();
+        let offset = (address - DDR_LAYOUT.base) as usize;
+        assert_eq!(offset % point_bytes, 0);
+        assert_eq!(data.len() % point_bytes, 0);
+        let first = offset / point_bytes;
+        self.dma_bytes += data.len();
+        for (i, bytes) in data.chunks_exact(point_bytes).enumerate() {
+            let mut packets = [Packet::default(); 3];
+            let coordinates = bytes.chunks_exact(DDR_LAYOUT.coordinate_bytes);
+            for (packet, bytes) in packets.iter_mut().zip(coordinates) {
+                for (limb, bytes) in packet.iter_mut().zip(bytes.chunks_exact(8)) {
+                    *limb = u64::from_le_bytes(bytes.try_into().unwrap());
+                }
+            }
+            let point = self.point_mut(first + i);
+            point.x = coordinate(&packets[0]);
+            point.y = coordinate(&packets[1]);
+            point.kt = coordinate(&packets[2]);
+        }
+        Ok(())
+    }
+}
+
+impl Flush for Sim {
+    fn flush(&mut self) {}
+}
+
+impl Write<u32> for Sim {
+    fn write(&mut self, index: usize, value: &u32) {
+        const QUERY: usize = WriteRegister::Query as _;
+        const DDR_READ_LEN: usize = WriteRegister::DdrReadLen as _;
+        const MSM_LENGTH: usize = WriteRegister::MsmLength as _;
+        const LAST_BUCKET: usize = WriteRegister::LastBucket as _;
+        const FIRST_BUCKET: usize = WriteRegister::FirstBucket as _;
+
+        match index {
+            QUERY => self.query = *value,
+            DDR_READ_LEN => self.ddr_read_len = *value,
+            MSM_LENGTH => self.msm_length = *value,
+            LAST_BUCKET => self.last_bucket = *value,
+            FIRST_BUCKET => self.first_bucket = *value,
+            _ => {}
+        }
+    }
+}
+
+impl ReadWrite<u32> for Sim {
+    fn read(&self, index: usize) -> u32 {
+        const STATISTIC: usize = ReadRegister::Statistic as _;
+        const DIGITS_QUEUE: usize = ReadRegister::DigitsQueue as _;
+        const AGGREGATED: usize = ReadRegister::Aggregated as _;
+        const X: usize = ReadRegister::X as _;
+        const Y: usize = ReadRegister::Y as _;
+        const Z: usize = ReadRegister::Z as _;
+        const T: usize = ReadRegister::T as _;
+
+        match index {
+            STATISTIC => self
+                .statistics
+                .get(self.query as usize)
+                .copied()
+                .unwrap_or(0),
+            DIGITS_QUEUE => 0,
+            AGGREGATED => self.aggregated as u32,
+            X => self.coordinate_word(0),
+            Y => self.coordinate_word(1),
+            Z => self.coordinate_word(2),
+            T => self.coordinate_word(3),
+            _ => 0,
+        }
+    }
+}
+
+impl Write<Packet> for Sim {
+    fn write(&mut self, index: usize, packet: &Packet) {
+        const SET_X: usize = Stream::SetX as _;
+        const SET_Y: usize = Stream::SetY as _;
+        const SET_KT: usize = Stream::SetKT as _;
+        const MSM: usize = Stream::Msm as _;
+        const SET_ZERO: usize = Stream::SetZero as _;
+        const SET_POINTS: usize = Stream::SetPoints as _;
+
+        let offset = index & STREAM_OFFSET_MASK;
+        match index & !STREAM_OFFSET_MASK {
+            SET_X => self.point_mut(offset).x = coordinate(packet),
+            SET_Y => self.point_mut(offset).y = coordinate(packet),
+            SET_KT => self.point_mut(offset).kt = coordinate(packet),
+            SET_POINTS => self.set_packed_limbs(offset, packet),
+            SET_ZERO => match offset {
+                0 => self.zero.x = coordinate(packet),
+                1 => self.zero.y = coordinate(packet),
+                2 => self.zero.z = coordinate(packet),
+                3 => self.zero.t = coordinate(packet),
+                _ => {}
+            },
+            MSM => {
+                for &cmd in packet.iter() {
+                    match cmd & COMMAND_MASK {
+                        c if c == Command::StartColumn as u64 => self.start_column(),
+                        c if c == Command::SetDigit as u64 => {
+                            self.set_digit((cmd >> 14) as u16 as i16)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        bls12_377::into_weierstrass,
+        testing::{harness_digits, harness_points, harness_scalars},
+        App,
+    };
+
+    #[test]
+    fn column() {
+        let size = 5;
+        let (beta, points) = harness_points(size);
+        let mut app = App::new(Sim::new(), size).unwrap();
+        app.set_preprocessed_points(&points).unwrap();
+
+        let (digits, sum) = harness_digits(&beta, size);
+        let mut packet = Packet::default();
+        let mut stream = app.start_column();
+        for chunk in digits.chunks(8) {
+            for (digit, cmd) in chunk.iter().zip(packet.iter_mut()) {
+                *cmd = Command::set_digit(*digit);
+            }
+            stream.write(&packet);
+        }
+
+        assert_eq!(into_weierstrass(&app.get_point().unwrap()), sum);
+    }
+
+    #[test]
+    fn msm() {
+        let size = 4;
+        let (beta, points) = harness_points(size);
+        let mut app = App::new(Sim::new(), size).unwrap();
+        app.set_preprocessed_points(&points).unwrap();
+
+        let (scalars, sum) = harness_scalars(&beta, size);
+        assert_eq!(app.msm(scalars.iter()).unwrap(), sum);
+    }
+}
@@ -0,0 +1,325 @@
+//! Host-side state of FPGAs, persisted across processes.
+//!
+//! Records which points are loaded on the FPGA in each slot, so that a restarted process
+//! can attach to preloaded points instead of uploading them again,
+//! cf. [`App::attach_preloaded`][crate::App::attach_preloaded].
+//!
+//! The state files live in [`STATE_DIR`][fpga::state::STATE_DIR], only accessible to root.
+//! They are removed on reboot, and by `fpga::f1::mgmt` when loading or clearing an image,
+//! which both clear the FPGA's DDR. Reloading an image by other means is not detected.
+
+use core::{fmt, str::FromStr};
+use std::{
+    fs::{DirBuilder, OpenOptions},
+    io::{Read as _, Write as _},
+    os::unix::fs::{DirBuilderExt as _, MetadataExt as _, OpenOptionsExt as _},
+    path::{Path, PathBuf},
+};
+
+use crate::{bls12_377::G1PTEAffine, Error, Result};
+
+/// Points per chunk of the fingerprint hash, which are hashed in parallel.
+const CHUNK_POINTS: usize = 1 << 16;
+
+/// Fingerprint of a point set: its length, and a non-cryptographic hash of its coordinates.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Fingerprint {
+    pub len: usize,
+    pub hash: u64,
+}
+
+const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a over 64-bit words, stable across platforms and Rust versions.
+fn hash(words: impl Iterator<Item = u64>) -> u64 {
+    update(OFFSET, words)
+}
+
+fn update(hash: u64, words: impl Iterator<Item = u64>) -> u64 {
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    words.fold(hash, |hash, word| (hash ^ word).wrapping_mul(PRIME))
+}
+
+fn words(points: &[G1PTEAffine]) -> impl Iterator<Item = u64> + '_ {
+    points.iter().flat_map(|point| {
+        [point.x, point.y, point.kt]
+            .into_iter()
+            .flat_map(|coordinate| coordinate.0 .0)
+    })
+}
+
+fn hash_points(points: &[G1PTEAffine]) -> u64 {
+    hash(words(points))
+}
+
+impl Fingerprint {
+    /// Fingerprint of the points, in the form loaded on the FPGA.
+    pub fn of(points: &[G1PTEAffine]) -> Self {
+        let chunks: Vec<_> = points.chunks(CHUNK_POINTS).collect();
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let per_thread = ((chunks.len() + threads - 1) / threads).max(1);
+        let hashes: Vec<u64> = std::thread::scope(|s| {
+            let handles: Vec<_> = chunks
+                .chunks(per_thread)
+                .map(|chunks| {
+                    s.spawn(move || {
+                        chunks
+                            .iter()
+                            .map(|chunk| hash_points(chunk))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        });
+        Fingerprint {
+            len: points.len(),
+            hash: hash(hashes.into_iter()),
+        }
+    }
+}
+
+/// Incremental [`Fingerprint::of`], for points arriving in pieces.
+#[derive(Clone, Debug)]
+pub struct FingerprintHasher {
+    len: usize,
+    // hash of the incomplete chunk
+    chunk: u64,
+    hashes: Vec<u64>,
+}
+
+impl Default for FingerprintHasher {
+    fn default() -> Self {
+        FingerprintHasher {
+            len: 0,
+            chunk: OFFSET,
+            hashes: Vec::new(),
+        }
+    }
+}
+
+impl FingerprintHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hash the next points.
+    pub fn update(&mut self, mut points: &[G1PTEAffine]) {
+        while !points.is_empty() {
+            let (head, tail) =
+                points.split_at((CHUNK_POINTS - self.len % CHUNK_POINTS).min(points.len()));
+            self.chunk = update(self.chunk, words(head));
+            self.len += head.len();
+            if self.len % CHUNK_POINTS == 0 {
+                self.hashes.push(self.chunk);
+                self.chunk = OFFSET;
+            }
+            points = tail;
+        }
+    }
+
+    pub fn finish(mut self) -> Fingerprint {
+        if self.len % CHUNK_POINTS != 0 {
+            self.hashes.push(self.chunk);
+        }
+        Fingerprint {
+            len: self.len,
+            hash: hash(self.hashes.into_iter()),
+        }
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{:016x}", self.len, self.hash)
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = ();
+
+    fn from_str(fingerprint: &str) -> core::result::Result<Self, ()> {
+        let (len, hash) = fingerprint.split_once(':').ok_or(())?;
+        Ok(Fingerprint {
+            len: len.parse().map_err(drop)?,
+            hash: u64::from_str_radix(hash, 16).map_err(drop)?,
+        })
+    }
+}
+
+/// State of an FPGA slot.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct State {
+    /// image the points were loaded with, if known
+    pub image_id: Option<String>,
+    pub fingerprint: Fingerprint,
+}
+
+/// Default state file of the given slot, cf. [`fpga::state::state_file`].
+pub fn state_file(slot: i32) -> PathBuf {
+    fpga::state::state_file(slot)
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> Error + '_ {
+    move |source| Error::Io {
+        name: path.display().to_string(),
+        source,
+    }
+}
+
+impl State {
+    /// Read the state file, `None` if there is none.
+    ///
+    /// Fails if the file is a symlink, or is owned by neither root nor the effective user,
+    /// who is root when using F1s.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        let mut file = match OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(path)
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(io_error(path)(err)),
+        };
+        let owner = file.metadata().map_err(io_error(path))?.uid();
+        if owner != 0 && owner != unsafe { libc::geteuid() } {
+            return Err(Error::UntrustedState(path.display().to_string()));
+        }
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).map_err(io_error(path))?;
+        let corrupted = || Error::CorruptedState(path.display().to_string());
+        let mut lines = contents.lines();
+        let image_id = match lines.next().ok_or_else(corrupted)? {
+            "" => None,
+            image_id => Some(image_id.to_string()),
+        };
+        let fingerprint = lines
+            .next()
+            .and_then(|fingerprint| fingerprint.parse().ok())
+            .ok_or_else(corrupted)?;
+        Ok(Some(State {
+            image_id,
+            fingerprint,
+        }))
+    }
+
+    /// Write the state file, replacing it atomically.
+    ///
+    /// Missing directories are created accessible only to the user, the file readable
+    /// only by them.
+    pub fn store(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir)
+                .map_err(io_error(dir))?;
+        }
+        let image_id = self.image_id.as_deref().unwrap_or("");
+        let tmp = path.with_extension("tmp");
+        // left over by an interrupted store; removes symlinks, not their targets
+        State::remove(&tmp)?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&tmp)
+            .map_err(io_error(&tmp))?;
+        write!(file, "{}\n{}\n", image_id, self.fingerprint).map_err(io_error(&tmp))?;
+        std::fs::rename(&tmp, path).map_err(io_error(path))
+    }
+
+    /// Remove the state file, e.g. before loading other points.
+    pub fn remove(path: &Path) -> Result<()> {
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(io_error(path)(err)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        testing::{harness_points, harness_scalars},
+        App, Sim,
+    };
+
+    #[test]
+    fn preloaded() {
+        let size = 4;
+        let (beta, points) = harness_points(size);
+        let (scalars, sum) = harness_scalars(&beta, size);
+        let path = std::env::temp_dir().join(format!("cyclone-state-{}", std::process::id()));
+        let fingerprint = Fingerprint::of(&points);
+        assert_eq!(fingerprint.to_string().parse(), Ok(fingerprint));
+        let many: Vec<_> = points
+            .iter()
+            .copied()
+            .cycle()
+            .take(2 * CHUNK_POINTS + 1)
+            .collect();
+        let mut hasher = FingerprintHasher::new();
+        for piece in [
+            &many[..5],
+            &many[5..CHUNK_POINTS + 7],
+            &many[CHUNK_POINTS + 7..],
+        ] {
+            hasher.update(piece);
+        }
+        assert_eq!(hasher.finish(), Fingerprint::of(&many));
+
+        let mut app = App::new(Sim::new(), size).unwrap();
+        app.set_state_file(Some(path.clone()));
+        app.set_preprocessed_points(&points).unwrap();
+        assert_eq!(app.fingerprint(), Some(fingerprint));
+        use std::os::unix::fs::PermissionsExt as _;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // symlinks are neither written through nor read
+        let target = path.with_extension("target");
+        std::fs::write(&target, "").unwrap();
+        std::os::unix::fs::symlink(&target, path.with_extension("tmp")).unwrap();
+        let state = State::load(&path).unwrap().unwrap();
+        state.store(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "");
+        let link = path.with_extension("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        assert!(matches!(State::load(&link), Err(Error::Io { .. })));
+        std::fs::remove_file(link).unwrap();
+        std::fs::remove_file(target).unwrap();
+
+        // restarted process, with the points still in DDR
+        let mut restarted = App::new(app.fpga.clone(), size).unwrap();
+        restarted.set_state_file(Some(path.clone()));
+        restarted.attach_preloaded(fingerprint).unwrap();
+        assert_eq!(restarted.msm(scalars.iter()).unwrap(), sum);
+
+        let mut other = points.clone();
+        other.swap(0, 1);
+        assert!(matches!(
+            restarted.attach_preloaded(Fingerprint::of(&other)),
+            Err(Error::PreloadedMismatch { found: Some(found), .. }) if found == fingerprint
+        ));
+
+        restarted
+            .set_preprocessed_point_repeatedly(&points[0])
+            .unwrap();
+        assert_eq!(restarted.fingerprint(), None);
+        assert!(matches!(
+            app.attach_preloaded(fingerprint),
+            Err(Error::PreloadedMismatch { found: None, .. })
+        ));
+    }
+}
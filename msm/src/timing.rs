@@ -1,6 +1,6 @@
 //! Timing utilities.
 
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
 #[cfg(feature = "timings")]
 #[inline]
@@ -26,3 +26,32 @@ pub fn always_timed<R>(name: &str, f: impl FnOnce() -> R) -> R {
     println!("   {:?}", t.elapsed().unwrap());
     r
 }
+
+/// Activity that took place between `start` and `end`.
+#[derive(Copy, Clone, Debug)]
+pub struct Span<A> {
+    pub activity: A,
+    pub start: Instant,
+    pub end: Instant,
+}
+
+impl<A> Span<A> {
+    pub fn until_now(activity: A, start: Instant) -> Self {
+        Self {
+            activity,
+            start,
+            end: Instant::now(),
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_duration_since(self.start)
+    }
+
+    /// Time during which both activities took place.
+    pub fn overlap<B>(&self, other: &Span<B>) -> Duration {
+        self.end
+            .min(other.end)
+            .saturating_duration_since(self.start.max(other.start))
+    }
+}